@@ -1,19 +1,29 @@
+use std::hash::{Hash, Hasher};
 use std::vec::Vec;
-use ::Grid::Grid;
+use ::Grid::{get_slide_attacks, AttackTable, Grid, RayTable, RAYS};
 use ::Piece::Piece;
-use ::util;
-
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+use ::PieceSet::PieceSet;
+use ::Rules::Rules;
+use ::util::HandLayout;
+
+// 盤面は先手から見た向きで表す。盤の大きさは Rules#width, Rules#height で決まる
+// BoardMap やデータベースでは手番側が先手になるように反転した盤面 (Board#normalize) だけを扱い、
+// 盤上の駒を動かす手や遷移先などを求めるメソッドも手番側を先手とみなして計算する
+#[derive(Clone, Copy, Debug)]
 pub struct Board {
-    // マスの状態
-    pub grids: u64,
+    // マスの状態 (マスの番号 y * 幅 + x ごとに5ビット)
+    pub grids: u128,
     // 持ち駒
     pub hands: u32,
-    // 手番 (先手/後手)
+    // 手番 (false: 先手, true: 後手)
     pub player: bool,
+    // 持ち駒の表現 (HandLayout#register で得たインデックス)
+    pub hand_layout: u8,
+    // 盤の大きさや成りに関するルール (Rules#register で得たインデックス)
+    pub rules: u8,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum BoardResult {
     Win,
     Lose,
@@ -22,18 +32,18 @@ pub enum BoardResult {
 
 #[derive(PartialEq, Eq, Debug)]
 struct BoardHandInfo {
-    first: Vec<u8>,
-    second: Vec<u8>,
+    first: [u8; 7],
+    second: [u8; 7],
 }
 
 // 座標
-#[derive(PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Coord {
     pub x: u8,
     pub y: u8,
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct PieceMove {
     pub piece: Piece,
     pub from: Coord,
@@ -42,43 +52,201 @@ pub struct PieceMove {
     pub promote: bool,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PieceDrop {
+    pub piece: Piece,
+    pub to: Coord,
+}
+
+// 指し手 (駒の移動または打ち駒)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Move(PieceMove),
+    Drop(PieceDrop),
+}
+
+impl Coord {
+    // 幅 width, 高さ height の盤面を180度回転させた座標
+    pub fn reverse(&self, width: u8, height: u8) -> Coord {
+        Coord {x: width - 1 - self.x, y: height - 1 - self.y}
+    }
+}
+
+impl Action {
+    // 幅 width, 高さ height の盤面を180度回転させた指し手
+    pub fn reverse(&self, width: u8, height: u8) -> Action {
+        match self {
+            Action::Move(mov) => Action::Move(PieceMove {
+                piece: mov.piece,
+                from: mov.from.reverse(width, height),
+                to: mov.to.reverse(width, height),
+                promote: mov.promote,
+            }),
+            Action::Drop(drop) => Action::Drop(PieceDrop {
+                piece: drop.piece,
+                to: drop.to.reverse(width, height),
+            }),
+        }
+    }
+}
+
+// Board#moves で返す、盤上の駒を動かす手のイテレータ
+pub struct Moves {
+    board: Board,
+    rules: &'static Rules,
+    pieces: &'static PieceSet,
+    // 駒の定義と盤の大きさに対応する移動先の表
+    slides: &'static [u8; 29],
+    step_attacks: &'static AttackTable,
+    rays: &'static RayTable,
+    // 先手の駒があるマス
+    first: u32,
+    // 駒があるマス
+    occupied: u32,
+    // 次に調べる移動元のマスの番号
+    square: usize,
+    // 移動中の駒
+    grid: Grid,
+    // 移動中の駒の残りの移動先
+    targets: u32,
+    // 直前に返した手の成る手
+    promotion: Option<PieceMove>,
+}
+
+// Board#drops で返す、持ち駒を打つ手のイテレータ
+pub struct Drops {
+    board: Board,
+    // 先手の持ち駒
+    hands: [u8; 7],
+    // 盤の幅
+    width: u8,
+    // 駒がないマス
+    empty: u32,
+    // 次に調べる持ち駒の種類
+    hand_index: usize,
+    // 打っている駒の残りの打ち先
+    targets: u32,
+}
+
+// Board#transitions で返す、遷移先の盤面のイテレータ
+pub struct Transitions {
+    board: Board,
+    moves: Moves,
+    drops: Drops,
+}
+
 impl Board {
     pub fn Empty() -> Board {
         Board {
             grids: 0,
             hands: 0,
             player: false,
+            hand_layout: 0,
+            rules: 0,
         }
     }
 
+    // 持ち駒の表現を layout に変えた盤面
+    pub fn with_hand_layout(&self, layout: &HandLayout) -> Board {
+        let mut board = Board {
+            grids: self.grids,
+            hands: 0,
+            player: self.player,
+            hand_layout: layout.register(),
+            rules: self.rules,
+        };
+
+        for i in 0..7 {
+            let piece = Piece::from_hand_index(i);
+            board = board.add_hand(0, piece, self.get_hand_count(0, piece) as i8);
+            board = board.add_hand(1, piece, self.get_hand_count(1, piece) as i8);
+        }
+
+        board
+    }
+
+    pub fn get_hand_layout(&self) -> &'static HandLayout {
+        HandLayout::get(self.hand_layout)
+    }
+
+    // ルールを rules に変えた盤面
+    // 盤の大きさの違うルールには、盤上に駒がない盤面だけを変えられる
+    pub fn with_rules(&self, rules: &Rules) -> Board {
+        let current = self.get_rules();
+        assert!(self.grids == 0 || (current.width == rules.width && current.height == rules.height), "Cannot change the board size of a non-empty board");
+
+        Board {
+            grids: self.grids,
+            hands: self.hands,
+            player: self.player,
+            hand_layout: self.hand_layout,
+            rules: rules.register(),
+        }
+    }
+
+    pub fn get_rules(&self) -> &'static Rules {
+        Rules::get(self.rules)
+    }
+
+    // マスの状態を書き出すバイト数 (3x3 の盤面では以前と同じく8バイト)
+    fn get_grids_size(rules: &Rules) -> usize {
+        if rules.get_squares() * 5 <= 64 {8} else {16}
+    }
+
     pub fn to_blob(&self) -> Vec<u8> {
-        vec![
-            ((self.grids >> (8 * 7)) & 0xff) as u8,
-            ((self.grids >> (8 * 6)) & 0xff) as u8,
-            ((self.grids >> (8 * 5)) & 0xff) as u8,
-            ((self.grids >> (8 * 4)) & 0xff) as u8,
-            ((self.grids >> (8 * 3)) & 0xff) as u8,
-            ((self.grids >> (8 * 2)) & 0xff) as u8,
-            ((self.grids >> (8 * 1)) & 0xff) as u8,
-            ((self.grids >> (8 * 0)) & 0xff) as u8,
-            ((self.hands >> (8 * 3)) & 0xff) as u8,
-            ((self.hands >> (8 * 2)) & 0xff) as u8,
-            ((self.hands >> (8 * 1)) & 0xff) as u8,
-            ((self.hands >> (8 * 0)) & 0xff) as u8,
-        ]
+        let grids_size = Board::get_grids_size(self.get_rules());
+        let mut blob: Vec<u8> = Vec::with_capacity(grids_size + 4);
+
+        for i in (0..grids_size).rev() {
+            blob.push(((self.grids >> (8 * i)) & 0xff) as u8);
+        }
+
+        for i in (0..4).rev() {
+            blob.push(((self.hands >> (8 * i)) & 0xff) as u8);
+        }
+
+        blob
+    }
+
+    // hand_layout と rules は書き出したときの盤面と同じものを指定する
+    pub fn from_blob(blob: &[u8], hand_layout: u8, rules: u8) -> Board {
+        let grids_size = Board::get_grids_size(Rules::get(rules));
+        assert!(blob.len() == grids_size + 4);
+
+        Board {
+            grids: blob[0..grids_size].iter().fold(0, |grids, &byte| (grids << 8) | byte as u128),
+            hands: blob[grids_size..].iter().fold(0, |hands, &byte| (hands << 8) | byte as u32),
+            player: false,
+            hand_layout,
+            rules,
+        }
+    }
+
+    // 盤の幅と高さ
+    pub fn get_size(&self) -> (u8, u8) {
+        let rules = self.get_rules();
+        (rules.width, rules.height)
+    }
+
+    // 座標 (x, y) のマスの番号
+    fn get_square(&self, x: u8, y: u8) -> usize {
+        let (width, height) = self.get_size();
+        assert!(x < width && y < height);
+        y as usize * width as usize + x as usize
     }
 
     pub fn get_grid(&self, x: u8, y: u8) -> Grid {
-        assert!(x < 3 && y < 3);
-        Grid::from_i(((self.grids >> ((y * 3 + x) * 5)) & 0b11111) as u8)
+        Grid::from_i(self.get_code(self.get_square(x, y)) as u8)
     }
 
     pub fn set_grid(&self, x: u8, y: u8, grid: Grid) -> Board {
-        assert!(x < 3 && y < 3);
+        let square = self.get_square(x, y);
         Board {
-            grids: (self.grids & !(0b11111 << ((y * 3 + x) * 5))) | ((grid.to_i() as u64 ) << ((y * 3 + x) * 5)),
+            grids: (self.grids & !(0b11111 << (square * 5))) | ((grid.to_i() as u128) << (square * 5)),
             hands: self.hands,
             player: self.player,
+            hand_layout: self.hand_layout,
+            rules: self.rules,
         }
     }
 
@@ -86,17 +254,14 @@ impl Board {
         self.set_grid(x, y, Grid {piece: Piece::Empty, player: 0, promoted: false})
     }
 
-    fn reverse_grids(grids: u64) -> u64 {
-        let mut new_grids: u64 = 0;
+    // 180度回転させるとマスの番号 square は squares - 1 - square になる
+    fn reverse_grids(grids: u128, squares: usize) -> u128 {
+        let mut new_grids: u128 = 0;
 
-        for y in 0..3 {
-            let new_y = 2 - y;
-            for x in 0..3 {
-                let new_x = 2 - x;
-                let mut grid = Grid::from_i(((grids >> ((y * 3 + x) * 5)) & 0b11111) as u8);
-                grid.player = if grid.player == 0 {1} else {0};
-                new_grids |= (grid.to_i() as u64) << ((new_y * 3 + new_x) * 5);
-            }
+        for square in 0..squares {
+            let mut grid = Grid::from_i(((grids >> (square * 5)) & 0b11111) as u8);
+            grid.player = if grid.player == 0 {1} else {0};
+            new_grids |= (grid.to_i() as u128) << ((squares - 1 - square) * 5);
         }
 
         new_grids
@@ -104,320 +269,319 @@ impl Board {
 
     fn get_hands(&self) -> BoardHandInfo {
         let mut hands = BoardHandInfo {
-            first: Vec::with_capacity(7),
-            second: Vec::with_capacity(7),
+            first: [0; 7],
+            second: [0; 7],
         };
 
-        let mut temp_hands = self.hands;
+        let layout = self.get_hand_layout();
+
         for hand_type in 0..7 {
-            let size = match hand_type {
-                0 | 1 => 3,
-                2 | 3 | 4 | 5  => 4,
-                6 => 6,
-                _ => panic!(),
-            };
-            let max_pieces = match hand_type {
-                0 | 1 => 2,
-                2 | 3 | 4 | 5 => 4,
-                6 => 7,
-                _ => panic!(),
-            };
-            let hand_info = util::hand_data_to_hand_info((temp_hands & ((1 << size) - 1)) as u8, max_pieces);
-            temp_hands >>= size;
-
-            hands.first.push(hand_info.first);
-            hands.second.push(hand_info.second);
+            let (first, second) = layout.get_hand_counts(self.hands, hand_type);
+            hands.first[hand_type] = first;
+            hands.second[hand_type] = second;
         }
 
         hands
     }
 
-    fn set_hands(&self, hand_info: BoardHandInfo) -> Board {
-        let mut hands: u32 = 0;
-        let mut offset = 0;
-
-        for hand_type in 0..7 {
-            let size = match hand_type {
-                0 | 1 => 3,
-                2 | 3 | 4 | 5  => 4,
-                6 => 6,
-                _ => panic!(),
-            };
-            let max_pieces = match hand_type {
-                0 | 1 => 2,
-                2 | 3 | 4 | 5 => 4,
-                6 => 7,
-                _ => panic!(),
-            };
-            let hand_data = util::hand_info_to_hand_data(util::HandInfo {
-                first: hand_info.first[hand_type],
-                second: hand_info.second[hand_type],
-            }, max_pieces);
-
-            assert!(hand_data < (1 << size));
-
-            hands |= (hand_data as u32) << offset;
-
-            offset += size;
-        }
-
+    pub fn add_hand(&self, player: u8, piece: Piece, count: i8) -> Board {
         Board {
             grids: self.grids,
-            hands: hands,
+            hands: self.get_hand_layout().add_hand(self.hands, piece.to_hand_index() as usize, player, count),
             player: self.player,
+            hand_layout: self.hand_layout,
+            rules: self.rules,
         }
     }
 
-    pub fn add_hand(&self, player: u8, piece: Piece, count: i8) -> Board {
-        let mut hands = self.get_hands();
-
-        let piece_index = match piece {
-            Piece::飛車 => 0,
-            Piece::角行 => 1,
-            Piece::金将 => 2,
-            Piece::銀将 => 3,
-            Piece::桂馬 => 4,
-            Piece::香車 => 5,
-            Piece::歩兵 => 6,
-            _ => panic!(),
-        };
+    pub fn get_hand_count(&self, player: u8, piece: Piece) -> u8 {
+        let (first, second) = self.get_hand_layout().get_hand_counts(self.hands, piece.to_hand_index() as usize);
 
         match player {
-            0 => {
-                assert!(hands.first[piece_index] as i8 + count >= 0);
-                hands.first[piece_index] = (hands.first[piece_index] as i8 + count) as u8
-            },
-            1 => {
-                assert!(hands.second[piece_index] as i8 + count >= 0);
-                hands.second[piece_index] = (hands.second[piece_index] as i8 + count) as u8
-            },
+            0 => first,
+            1 => second,
             _ => panic!(),
-        };
-
-        self.set_hands(hands)
+        }
     }
 
     fn reverse_hands(&self) -> Board {
-        let hands = self.get_hands();
-        let new_hands = BoardHandInfo {
-            first: hands.second,
-            second: hands.first,
-        };
-        self.set_hands(new_hands)
+        Board {
+            grids: self.grids,
+            hands: self.get_hand_layout().reverse_hands(self.hands),
+            player: self.player,
+            hand_layout: self.hand_layout,
+            rules: self.rules,
+        }
     }
 
+    // 先手と後手を入れ替えた盤面 (手番はそのまま)
     pub fn reverse(&self) -> Board {
         Board {
-            grids: Board::reverse_grids(self.grids),
+            grids: Board::reverse_grids(self.grids, self.get_rules().get_squares()),
             hands: self.reverse_hands().hands,
-            player: self.player, // player is unused currently
+            player: self.player,
+            hand_layout: self.hand_layout,
+            rules: self.rules,
         }
     }
 
-    pub fn get_possible_moves(&self) -> Vec<PieceMove> {
-        let mut moves: Vec<PieceMove> = Vec::with_capacity(128);
+    // 手番 (0: 先手, 1: 後手)
+    pub fn side_to_move(&self) -> u8 {
+        if self.player {1} else {0}
+    }
 
-        for y in 0..3 {
-            for x in 0..3 {
-                let grid = self.get_grid(x, y);
+    // 手番を player にした盤面
+    pub fn with_side_to_move(&self, player: u8) -> Board {
+        Board {
+            grids: self.grids,
+            hands: self.hands,
+            player: player == 1,
+            hand_layout: self.hand_layout,
+            rules: self.rules,
+        }
+    }
 
-                if grid.piece == Piece::Empty || grid.player == 1 {
-                    continue;
-                }
+    // 手番側が先手になるように反転した盤面
+    pub fn normalize(&self) -> Board {
+        if self.player {
+            self.reverse().with_side_to_move(0)
+        } else {
+            *self
+        }
+    }
 
-                let piece_moves = grid.get_moves();
+    // normalize した盤面での指し手と、この盤面での指し手を相互に変換する
+    pub fn normalize_action(&self, action: Action) -> Action {
+        if self.player {
+            let (width, height) = self.get_size();
+            action.reverse(width, height)
+        } else {
+            action
+        }
+    }
 
-                for piece_move in piece_moves {
-                    let target_x = x as i8 + piece_move.x;
-                    let target_y = y as i8 + piece_move.y;
+    // 手番側の合法手 (Board#get_legal_actions と同じ順番)
+    pub fn legal_moves(&self) -> Vec<Action> {
+        self.normalize().get_legal_actions().into_iter().map(|action| self.normalize_action(action)).collect()
+    }
 
-                    // 移動先が盤外
-                    if !(0..3).contains(&target_x) || !(0..3).contains(&target_y) {
-                        continue;
-                    }
+    // 手番側が指し手を指した後の盤面 (先手から見た向きのまま手番を入れ替える)
+    pub fn apply(&self, action: &Action) -> Board {
+        let board = self.normalize().apply_action(&self.normalize_action(*action));
 
-                    let target_grid = self.get_grid(target_x as u8, target_y as u8);
+        if self.player {
+            board
+        } else {
+            board.reverse().with_side_to_move(1)
+        }
+    }
 
-                    // 移動先に自分の駒がある
-                    if target_grid.piece != Piece::Empty && target_grid.player == 0 {
-                        continue;
-                    }
+    // マスの番号 (y * 幅 + x) の Grid#to_i の値
+    fn get_code(&self, square: usize) -> usize {
+        ((self.grids >> (square * 5)) & 0b11111) as usize
+    }
 
-                    // 大駒は他の駒を飛び越えられない
-                    if piece_move.x % 2 == 0 && piece_move.y % 2 == 0 {
-                        // 中間点
-                        let intermediate_x = x as i8 + piece_move.x / 2;
-                        let intermediate_y = y as i8 + piece_move.y / 2;
-                        let intermediate_grid = self.get_grid(intermediate_x as u8, intermediate_y as u8);
+    // 先手と後手の駒があるマスをマスの番号のビットで表したもの
+    pub fn get_bitboards(&self) -> (u32, u32) {
+        let mut first: u32 = 0;
+        let mut second: u32 = 0;
 
-                        if intermediate_grid.piece != Piece::Empty {
-                            continue;
-                        }
-                    }
+        for square in 0..self.get_rules().get_squares() {
+            match self.get_code(square) {
+                0 => {},
+                1..=14 => first |= 1 << square,
+                _ => second |= 1 << square,
+            }
+        }
 
-                    // 行き所のない駒
-                    let force_promotion = if grid.promoted {
-                        false
-                    } else if target_y == 0 && (grid.piece == Piece::歩兵 || grid.piece == Piece::香車 || grid.piece == Piece::桂馬) {
-                        true
-                    } else if target_y == 1 && grid.piece == Piece::桂馬 {
-                        true
-                    } else {
-                        false
-                    };
-
-                    moves.push(PieceMove {
-                        from: Coord {
-                            x: x,
-                            y: y,
-                        },
-                        to: Coord {
-                            x: target_x as u8,
-                            y: target_y as u8,
-                        },
-                        piece: grid.piece,
-                        promote: force_promotion || grid.promoted,
-                    });
+        (first, second)
+    }
 
-                    if !force_promotion && (y == 0 || target_y == 0) && grid.is_promotable() {
-                        moves.push(PieceMove {
-                            from: Coord {
-                                x: x,
-                                y: y,
-                            },
-                            to: Coord {
-                                x: target_x as u8,
-                                y: target_y as u8,
-                            },
-                            piece: grid.piece,
-                            promote: true,
-                        });
-                    }
-                }
-            }
+    // 盤上の駒を動かす手 (王を取る手も含む)
+    pub fn moves(&self) -> Moves {
+        let (first, second) = self.get_bitboards();
+        let rules = self.get_rules();
+        let (width, height) = (rules.width as usize, rules.height as usize);
+        let tables = PieceSet::get_tables(rules.pieces);
+
+        Moves {
+            board: *self,
+            rules,
+            pieces: rules.get_piece_set(),
+            slides: &tables.slides,
+            step_attacks: &tables.step_attacks[width - 1][height - 1],
+            rays: &RAYS[width - 1][height - 1],
+            first,
+            occupied: first | second,
+            square: 0,
+            grid: Grid::from_i(0),
+            targets: 0,
+            promotion: None,
         }
+    }
+
+    pub fn get_possible_moves(&self) -> Vec<PieceMove> {
+        let mut moves: Vec<PieceMove> = Vec::with_capacity(128);
+        moves.extend(self.moves());
         moves
     }
 
-    pub fn get_result(&self) -> BoardResult {
-        let moves = self.get_possible_moves();
-        let hands = self.get_hands();
+    // 持ち駒を打つ手 (行き所のない駒と二歩を除く)
+    // 持ち駒を打てないルールでは何も返さない
+    pub fn drops(&self) -> Drops {
+        let (first, second) = self.get_bitboards();
+        let rules = self.get_rules();
+
+        Drops {
+            board: *self,
+            hands: self.get_hands().first,
+            width: rules.width,
+            empty: !(first | second) & ((1 << rules.get_squares()) - 1),
+            hand_index: if rules.allow_drops {0} else {7},
+            targets: 0,
+        }
+    }
 
-        // ステルスメイト
-        if moves.len() == 0 {
-            let mut is_stealth_mate = true;
-
-            // 打ち駒を打てる場所があるか
-            'hand_loop: for (i, &count) in hands.first.iter().enumerate() {
-                if count > 0 {
-                    let piece = Piece::from_hand_index(i);
-                    for y in 0..3 {
-                        for x in 0..3 {
-                            let grid = self.get_grid(x, y);
-                            if grid.piece != Piece::Empty {
-                                continue;
-                            }
-
-                            let board = self.set_grid(x, y, Grid {piece: piece, player: 0, promoted: false}).add_hand(0, piece, -1);
-
-                            if board.is_valid() {
-                                is_stealth_mate = false;
-                                break 'hand_loop;
-                            }
-                        }
-                    }
-                }
-            }
+    pub fn get_possible_drops(&self) -> Vec<PieceDrop> {
+        let mut drops: Vec<PieceDrop> = Vec::with_capacity(64);
+        drops.extend(self.drops());
+        drops
+    }
 
-            if is_stealth_mate {
-                return BoardResult::Lose;
-            }
+    // 駒を動かした盤面 (手番はそのまま)
+    // 持ち駒を打てないルールでは取った駒は持ち駒にならずに取り除かれる
+    pub fn move_piece(&self, mov: &PieceMove) -> Board {
+        let target_grid = self.get_grid(mov.to.x, mov.to.y);
+        let board = self.del_grid(mov.from.x, mov.from.y).set_grid(mov.to.x, mov.to.y, Grid {piece: mov.piece, promoted: mov.promote, player: 0});
+
+        if target_grid.player == 1 && self.get_rules().allow_drops {
+            board.add_hand(0, target_grid.piece, 1)
+        } else {
+            board
         }
+    }
 
-        for mov in moves {
-            let target_grid = self.get_grid(mov.to.x, mov.to.y);
+    // 駒を打った盤面 (手番はそのまま)
+    pub fn drop_piece(&self, drop: &PieceDrop) -> Board {
+        self.set_grid(drop.to.x, drop.to.y, Grid {piece: drop.piece, player: 0, promoted: false}).add_hand(0, drop.piece, -1)
+    }
 
-            if target_grid.piece == Piece::王将 && target_grid.player == 1 {
-                return BoardResult::Win;
-            }
+    // 指し手を指した後の盤面 (相手の手番になるので反転する)
+    pub fn apply_action(&self, action: &Action) -> Board {
+        match action {
+            Action::Move(mov) => self.move_piece(mov).reverse(),
+            Action::Drop(drop) => self.drop_piece(drop).reverse(),
+        }
+    }
+
+    pub fn get_result(&self) -> BoardResult {
+        if self.can_capture_king() {
+            return BoardResult::Win;
+        }
+
+        // ステルスメイト (勝敗はルールによる)
+        if self.is_stalemate() {
+            return self.get_rules().get_stalemate_result();
         }
 
         BoardResult::Unknown
     }
 
+    // 手番側が相手の王将を取れる盤面かを判定する (この盤面に遷移する手は非合法手)
+    // ステルスメイトを勝ちとするルールでも、ステルスメイトの盤面に遷移する手は合法手なので Board#get_result とは区別する
+    pub fn can_capture_king(&self) -> bool {
+        self.moves().any(|mov| {
+            let target_grid = self.get_grid(mov.to.x, mov.to.y);
+            target_grid.piece == Piece::王将 && target_grid.player == 1
+        })
+    }
+
+    // 盤上の駒を動かす手も、打ち歩詰めにならない持ち駒を打つ手もない盤面かを判定する
+    pub fn is_stalemate(&self) -> bool {
+        if self.moves().next().is_some() {
+            return false;
+        }
+
+        self.drops().all(|drop| drop.piece == Piece::歩兵 && self.drop_piece(&drop).reverse().is_mated())
+    }
+
+    // 王を取る手を除いたすべての手を指した後の盤面 (相手の手番になるので反転している)
+    pub fn transitions(&self) -> Transitions {
+        Transitions {
+            board: *self,
+            moves: self.moves(),
+            drops: self.drops(),
+        }
+    }
+
     pub fn get_possible_transitions(&self) -> Vec<Board> {
         let mut boards: Vec<Board> = Vec::with_capacity(256);
-        let moves = self.get_possible_moves();
+        boards.extend(self.transitions());
+        boards
+    }
 
-        for mov in moves {
-            let target_grid = self.get_grid(mov.to.x, mov.to.y);
+    // 相手に王を取られる手と打ち歩詰めを除いた、実際に指すことのできる手
+    pub fn get_legal_actions(&self) -> Vec<Action> {
+        let mut actions: Vec<Action> = Vec::with_capacity(128);
 
-            if target_grid.piece == Piece::王将 {
+        for mov in self.get_possible_moves() {
+            if self.get_grid(mov.to.x, mov.to.y).piece == Piece::王将 {
                 continue;
             }
 
-            let new_board = self.del_grid(mov.from.x, mov.from.y).set_grid(mov.to.x, mov.to.y, Grid {piece: mov.piece, promoted: mov.promote, player: 0});
+            actions.push(Action::Move(mov));
+        }
 
-            if target_grid.player == 1 {
-                boards.push(new_board.add_hand(0, target_grid.piece, 1).reverse());
-            } else {
-                boards.push(new_board.reverse());
-            }
+        for drop in self.get_possible_drops() {
+            actions.push(Action::Drop(drop));
         }
 
-        let hands = self.get_hands();
+        actions.into_iter().filter(|action| {
+            let transition = self.apply_action(action);
 
-        // 打ち駒
-        for (i, &count) in hands.first.iter().enumerate() {
-            if count > 0 {
-                let piece = Piece::from_hand_index(i);
-                for y in 0..3 {
-                    for x in 0..3 {
-                        let grid = self.get_grid(x, y);
-                        if grid.piece != Piece::Empty {
-                            continue;
-                        }
-
-                        let board = self.set_grid(x, y, Grid {piece: piece, player: 0, promoted: false}).add_hand(0, piece, -1);
+            if transition.can_capture_king() {
+                return false;
+            }
 
-                        if board.is_valid() {
-                            boards.push(board.reverse());
-                        }
-                    }
-                }
+            match action {
+                Action::Drop(drop) if drop.piece == Piece::歩兵 => !transition.is_mated(),
+                _ => true,
             }
-        }
+        }).collect()
+    }
 
-        boards
+    // 手番側がどう指しても次に王を取られる盤面 (詰みまたはステルスメイト) かを判定する
+    pub fn is_mated(&self) -> bool {
+        match self.get_result() {
+            BoardResult::Lose => true,
+            BoardResult::Win => false,
+            // 引き分けとするルールのステルスメイトは詰みではない
+            BoardResult::Unknown => {
+                !self.is_stalemate() && self.transitions().all(|transition| transition.get_result() == BoardResult::Win)
+            },
+        }
     }
 
     pub fn is_valid(&self) -> bool {
-        for y in 0..3 {
-            for x in 0..3 {
+        let rules = self.get_rules();
+        let (width, height) = (rules.width, rules.height);
+
+        for y in 0..height {
+            for x in 0..width {
                 let grid = self.get_grid(x, y);
                 if grid.piece == Piece::Empty {
                     continue;
                 }
 
                 // 行き所のない駒
-                if !grid.promoted && (
-                    (grid.player == 0 && y == 0) ||
-                    (grid.player == 1 && y == 2)
-                ) && (
-                    grid.piece == Piece::歩兵 ||
-                    grid.piece == Piece::香車 ||
-                    grid.piece == Piece::桂馬
-                ) {
-                    return false;
-                }
-
-                if !grid.promoted && y == 1 && grid.piece == Piece::桂馬 {
+                let row = if grid.player == 0 {y} else {height - 1 - y};
+                if !grid.promoted && row < rules.get_forced_promotion_rows(grid.piece) {
                     return false;
                 }
 
                 // 二歩
                 if grid.piece == Piece::歩兵 && grid.promoted == false {
-                    for target_y in (y + 1)..3 {
+                    for target_y in (y + 1)..height {
                         let target_grid = self.get_grid(x, target_y);
                         if target_grid.piece == Piece::歩兵 && target_grid.promoted == false && target_grid.player == grid.player {
                             return false;
@@ -449,9 +613,10 @@ impl Board {
         let mut e_flag = true;
         let mut f_flag = true;
         let mut m_flag = true;
+        let (width, height) = self.get_size();
 
-        for y in 0..3 {
-            for x in 0..3 {
+        for y in 0..height {
+            for x in 0..width {
                 let grid = self.get_grid(x, y);
 
                 if grid.promoted {
@@ -470,7 +635,7 @@ impl Board {
                     c_flag = false;
                 }
 
-                if y != 2 && grid.player == 0 {
+                if y != height - 1 && grid.player == 0 {
                     c_flag = false;
                 }
 
@@ -478,7 +643,7 @@ impl Board {
                     d_flag = false;
                 }
 
-                if y != 2 && grid.player == 1 {
+                if y != height - 1 && grid.player == 1 {
                     d_flag = false;
                 }
             }
@@ -501,17 +666,21 @@ impl Board {
         (a_flag && (b_count <= 1 || (m_flag && c_flag) || (m_flag && d_flag))) || (e_flag && f_flag)
     }
 
+    // 持ち駒の歩兵が1枚減る遷移 (歩兵を取った場合は増えるので、引き算で桁あふれしないように比べる)
     pub fn is_transition_打ち歩(&self, transition: Board) -> bool {
         let from_hands = self.get_hands();
         let to_hands = transition.get_hands();
-        from_hands.first[6] - to_hands.second[6] == 1
+        from_hands.first[6] == to_hands.second[6] + 1
     }
 
     pub fn print(&self) {
-        for y in 0..3 {
-            for x in 0..3 {
+        let (width, height) = self.get_size();
+        let piece_set = self.get_rules().get_piece_set();
+
+        for y in 0..height {
+            for x in 0..width {
                 let grid = self.get_grid(x, y);
-                print!("{}", grid.to_string());
+                print!("{}{}", if grid.player == 0 {" "} else {"v"}, piece_set.get_name(grid.piece, grid.promoted));
             }
 
             println!("");
@@ -526,7 +695,7 @@ impl Board {
             let piece = Piece::from_hand_index(i);
 
             for _ in 0..count {
-                print!("{}", piece.to_char());
+                print!("{}", piece_set.get_name(piece, false));
             }
 
             total_count += count;
@@ -545,7 +714,7 @@ impl Board {
             let piece = Piece::from_hand_index(i);
 
             for _ in 0..count {
-                print!("{}", piece.to_char());
+                print!("{}", piece_set.get_name(piece, false));
             }
 
             total_count += count;
@@ -559,3 +728,149 @@ impl Board {
         println!("");
     }
 }
+
+// 同じ局面を表す盤面は手番の向きによらず等しい
+impl PartialEq for Board {
+    fn eq(&self, other: &Board) -> bool {
+        let board = self.normalize();
+        let other = other.normalize();
+        board.grids == other.grids && board.hands == other.hands && board.hand_layout == other.hand_layout && board.rules == other.rules
+    }
+}
+
+impl Eq for Board {}
+
+impl Hash for Board {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let board = self.normalize();
+        // 3x3 の盤面ではマスの状態は下位64ビットに収まる
+        (board.grids as u64).hash(state);
+        if board.grids >> 64 != 0 {
+            ((board.grids >> 64) as u64).hash(state);
+        }
+        board.hands.hash(state);
+        board.hand_layout.hash(state);
+        board.rules.hash(state);
+    }
+}
+
+impl Iterator for Moves {
+    type Item = PieceMove;
+
+    fn next(&mut self) -> Option<PieceMove> {
+        loop {
+            if let Some(mov) = self.promotion.take() {
+                return Some(mov);
+            }
+
+            if self.targets != 0 {
+                let square = self.square - 1;
+                let target = self.targets.trailing_zeros() as usize;
+                self.targets &= self.targets - 1;
+
+                let grid = self.grid;
+                let width = self.rules.width as usize;
+                let from = Coord {x: (square % width) as u8, y: (square / width) as u8};
+                let to = Coord {x: (target % width) as u8, y: (target / width) as u8};
+
+                let is_promotable = !grid.promoted && self.pieces.is_promotable(grid.piece);
+                let can_promote = is_promotable && (from.y < self.rules.promotion_rows || to.y < self.rules.promotion_rows);
+
+                // 行き所のない駒と、不成を指せないルールで成れる駒
+                let force_promotion = is_promotable && (
+                    to.y < self.rules.get_forced_promotion_rows(grid.piece) ||
+                    (can_promote && !self.rules.allow_non_promotion)
+                );
+
+                if !force_promotion && can_promote {
+                    self.promotion = Some(PieceMove {
+                        from,
+                        to,
+                        piece: grid.piece,
+                        promote: true,
+                    });
+                }
+
+                return Some(PieceMove {
+                    from,
+                    to,
+                    piece: grid.piece,
+                    promote: force_promotion || grid.promoted,
+                });
+            }
+
+            if self.square >= self.rules.get_squares() {
+                return None;
+            }
+
+            let square = self.square;
+            let code = self.board.get_code(square);
+            self.square += 1;
+
+            if code == 0 || code >= 15 {
+                continue;
+            }
+
+            let mut targets = self.step_attacks[code][square];
+
+            // 走り駒は他の駒を飛び越えられない
+            if self.slides[code] != 0 {
+                targets |= get_slide_attacks(self.rays, square, self.slides[code], self.occupied);
+            }
+
+            // 移動先に自分の駒がある
+            self.targets = targets & !self.first;
+            self.grid = Grid::from_i(code as u8);
+        }
+    }
+}
+
+impl Iterator for Drops {
+    type Item = PieceDrop;
+
+    fn next(&mut self) -> Option<PieceDrop> {
+        loop {
+            if self.targets != 0 {
+                let target = self.targets.trailing_zeros() as u8;
+                self.targets &= self.targets - 1;
+
+                let drop = PieceDrop {
+                    piece: Piece::from_hand_index(self.hand_index - 1),
+                    to: Coord {x: target % self.width, y: target / self.width},
+                };
+
+                if self.board.drop_piece(&drop).is_valid() {
+                    return Some(drop);
+                }
+
+                continue;
+            }
+
+            if self.hand_index >= 7 {
+                return None;
+            }
+
+            if self.hands[self.hand_index] > 0 {
+                self.targets = self.empty;
+            }
+
+            self.hand_index += 1;
+        }
+    }
+}
+
+impl Iterator for Transitions {
+    type Item = Board;
+
+    fn next(&mut self) -> Option<Board> {
+        for mov in &mut self.moves {
+            if self.board.get_grid(mov.to.x, mov.to.y).piece == Piece::王将 {
+                continue;
+            }
+
+            return Some(self.board.move_piece(&mov).reverse());
+        }
+
+        self.drops.next().map(|drop| self.board.drop_piece(&drop).reverse())
+    }
+}
@@ -2,12 +2,22 @@ extern crate fnv;
 extern crate rusqlite;
 
 use self::fnv::FnvHashMap;
-use self::rusqlite::Connection;
-use self::rusqlite::types::Null;
+use self::rusqlite::{Connection, OpenFlags};
 use self::rusqlite::params;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 use ::Board::{Board, BoardResult};
 use ::Piece::Piece;
 use ::Grid::Grid;
+use ::Material::Material;
+use ::Progress::{Progress, SilentProgress, Stage};
+use ::Rules::{Rules, Stalemate, DEFAULT_RULES};
+use ::Tablebase::Tablebase;
+use ::Usi::to_sfen;
+use ::util::HandLayout;
+
+// BoardMap#write で1つのトランザクションにまとめて書き出す盤面の数
+const WRITE_BATCH_SIZE: usize = 100000;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct BoardState {
@@ -33,32 +43,50 @@ impl BoardMap {
     }
 
     pub fn from_pieces(pieces: Vec<Piece>) -> BoardMap {
-        let board = Board::Empty();
+        BoardMap::from_pieces_with_rules(pieces, &DEFAULT_RULES)
+    }
+
+    pub fn from_pieces_with_rules(pieces: Vec<Piece>, rules: &Rules) -> BoardMap {
+        BoardMap::from_pieces_with_progress(pieces, rules, &mut SilentProgress)
+    }
+
+    // 持ち駒の表現は駒構成から HandLayout::from_pieces_with_capacities で求める (表現できない駒構成の場合は panic する)
+    // 生成した盤面の数を progress に報告する
+    pub fn from_pieces_with_progress(pieces: Vec<Piece>, rules: &Rules, progress: &mut dyn Progress) -> BoardMap {
+        let hand_layout = match HandLayout::from_pieces_with_capacities(&pieces, rules.get_piece_set().get_capacities()) {
+            Ok(hand_layout) => hand_layout,
+            Err(message) => panic!("{}", message),
+        };
+        let board = Board::Empty().with_hand_layout(&hand_layout).with_rules(rules);
         let mut board_map = BoardMap::Empty();
 
+        progress.start(Stage::Generation, None);
+
         // まず先手後手の王将を置く
-        for x in 0..3 {
-            for y in 0..3 {
+        for x in 0..rules.width {
+            for y in 0..rules.height {
                 let board = board.set_grid(x, y, Grid {piece: Piece::王将, player: 0, promoted: false});
 
-                for x in 0..3 {
-                    for y in 0..3 {
+                for x in 0..rules.width {
+                    for y in 0..rules.height {
                         if board.get_grid(x, y).piece != Piece::Empty {
                             continue;
                         }
                         let board = board.set_grid(x, y, Grid {piece: Piece::王将, player: 1, promoted: false});
 
-                        board_map.place_pieces(board, &pieces);
+                        board_map.place_pieces(board, &pieces, progress);
                     }
                 }
             }
         }
 
+        progress.finish();
+
         board_map
     }
 
     // 王将以外の駒を配置する
-    fn place_pieces(&mut self, board: Board, pieces: &Vec<Piece>) {
+    fn place_pieces(&mut self, board: Board, pieces: &Vec<Piece>, progress: &mut dyn Progress) {
         if !board.is_valid() {
             return;
         }
@@ -93,35 +121,296 @@ impl BoardMap {
                 });
             }
 
-            if self.map.len() % 100000 == 0 {
-                println!("BoardMap#place_pieces: {} boards completed", self.map.len());
-            }
+            progress.update(self.map.len());
 
             return;
         }
 
         let (&piece, rest_pieces) = pieces.split_first().unwrap();
+        let (width, height) = board.get_size();
 
         // 駒を置く
-        for x in 0..3 {
-            for y in 0..3 {
+        for x in 0..width {
+            for y in 0..height {
                 if board.get_grid(x, y).piece != Piece::Empty {
                     continue;
                 }
 
-                self.place_pieces(board.set_grid(x, y, Grid {piece: piece, player: 0, promoted: false}), &rest_pieces.to_vec());
-                self.place_pieces(board.set_grid(x, y, Grid {piece: piece, player: 1, promoted: false}), &rest_pieces.to_vec());
+                self.place_pieces(board.set_grid(x, y, Grid {piece: piece, player: 0, promoted: false}), &rest_pieces.to_vec(), progress);
+                self.place_pieces(board.set_grid(x, y, Grid {piece: piece, player: 1, promoted: false}), &rest_pieces.to_vec(), progress);
+
+                if board.get_rules().get_piece_set().is_promotable(piece) {
+                    self.place_pieces(board.set_grid(x, y, Grid {piece: piece, player: 0, promoted: true}), &rest_pieces.to_vec(), progress);
+                    self.place_pieces(board.set_grid(x, y, Grid {piece: piece, player: 1, promoted: true}), &rest_pieces.to_vec(), progress);
+                }
+            }
+        }
+
+        if board.get_rules().allow_drops {
+            // 持ち駒にする
+            self.place_pieces(board.add_hand(0, piece, 1), &rest_pieces.to_vec(), progress);
+            self.place_pieces(board.add_hand(1, piece, 1), &rest_pieces.to_vec(), progress);
+        } else {
+            // 持ち駒を打てないルールでは、取られた駒は盤上から取り除かれる
+            self.place_pieces(board, &rest_pieces.to_vec(), progress);
+        }
+    }
+
+    // 遷移先の状態から盤面の状態を求める。打ち歩詰めになる手は指せない手として扱う
+    // 勝敗が決まらない場合は Unknown を返す。遷移先が見つからない場合はその盤面を Err で返す
+    // 返り値の2つ目は打ち歩詰めになる手の数
+    pub fn evaluate(&self, board: &Board) -> Result<(BoardState, u32), Board> {
+        self.evaluate_until(board, u8::MAX)
+    }
+
+    // BoardMap#evaluate と同じだが、深さが max_depth を超える遷移先はまだ勝敗が決まっていないものとして扱う
+    // BoardMap#solve の探索で、それまでの回に決まっているはずの盤面だけを使うためのもの
+    pub fn evaluate_until(&self, board: &Board, max_depth: u8) -> Result<(BoardState, u32), Board> {
+        let mut transition_count = 0_u16;
+
+        let mut is_all_win = true;
+        let mut is_any_lose = false;
+        let mut min_lose_depth = None;
+        let mut max_win_depth = None;
+        let mut win_routes = 0_u32;
+        let mut lose_routes = 0_u32;
+        // 非合法手の数
+        let mut win_0_count = 0_u16;
+        let mut 打ち歩詰め_count = 0_u32;
+
+        for transition in board.transitions() {
+            transition_count += 1;
+
+            let transition_state = match self.map.get(&transition) {
+                Some(&state) => state,
+                // 深さ0の盤面はデータベースに書き出されないので、読み込んだ盤面には含まれないことがある
+                None if transition.get_result() == BoardResult::Win => BoardState {
+                    result: BoardResult::Win,
+                    depth: Some(0),
+                    routes: Some(1),
+                    is_good: Some(false),
+                },
+                None => return Err(transition),
+            };
+
+            let transition_state = match transition_state.depth {
+                Some(depth) if depth > max_depth => BoardState {
+                    result: BoardResult::Unknown,
+                    depth: None,
+                    routes: None,
+                    is_good: None,
+                },
+                _ => transition_state,
+            };
+
+            match transition_state.result {
+                BoardResult::Win => {
+                    let new_depth = transition_state.depth.expect("Depth of the transition was not set");
+                    let new_routes = transition_state.routes.expect("Routes of the transition was not set");
+
+                    match max_win_depth {
+                        None => {
+                            max_win_depth = Some(new_depth);
+                            win_routes = new_routes;
+                        },
+                        Some(depth) => {
+                            if new_depth == depth {
+                                win_routes += new_routes;
+                            } else if new_depth > depth {
+                                max_win_depth = Some(new_depth);
+                                win_routes = new_routes;
+                            }
+                        },
+                    }
+
+                    // ステルスメイトを勝ちとするルールでは、相手をステルスメイトにする手も深さ0の勝ちになるが合法手として数える
+                    if new_depth == 0 && (board.get_rules().stalemate != Stalemate::Win || transition.can_capture_king()) {
+                        win_0_count += 1;
+                    }
+                },
+                BoardResult::Lose => {
+                    let new_depth = transition_state.depth.expect("Depth of the transition was not set");
+                    let new_routes = transition_state.routes.expect("Routes of the transition was not set");
+
+                    // 打ち歩詰め
+                    if new_depth == 1 && board.is_transition_打ち歩(transition) {
+                        打ち歩詰め_count += 1;
+                        win_0_count += 1;
+                        continue;
+                    }
+
+                    is_all_win = false;
+                    is_any_lose = true;
+
+                    match min_lose_depth {
+                        None => {
+                            min_lose_depth = Some(new_depth);
+                            lose_routes = new_routes;
+                        },
+                        Some(depth) => {
+                            if new_depth == depth {
+                                lose_routes += new_routes;
+                            } else if new_depth < depth {
+                                min_lose_depth = Some(new_depth);
+                                lose_routes = new_routes;
+                            }
+                        },
+                    }
+                },
+                BoardResult::Unknown => {
+                    is_all_win = false;
+                },
+            }
+        }
+
+        let state = if is_all_win {
+            match max_win_depth {
+                Some(depth) => BoardState {
+                    result: BoardResult::Lose,
+                    depth: Some(depth + 1),
+                    routes: Some(win_routes),
+                    is_good: Some(false),
+                },
+                // 指せる手がない (すべて打ち歩詰めの場合も含む) 盤面はステルスメイトのルールに従う
+                // 勝ち負けになるルールでは Board#get_result で決まるので、ここに来るのは引き分けの場合
+                None => match board.get_rules().get_stalemate_result() {
+                    BoardResult::Lose => BoardState {
+                        result: BoardResult::Lose,
+                        depth: Some(1),
+                        routes: Some(1),
+                        is_good: Some(false),
+                    },
+                    BoardResult::Win => BoardState {
+                        result: BoardResult::Win,
+                        depth: Some(0),
+                        routes: Some(1),
+                        is_good: Some(false),
+                    },
+                    BoardResult::Unknown => BoardState {
+                        result: BoardResult::Unknown,
+                        depth: None,
+                        routes: None,
+                        is_good: None,
+                    },
+                },
+            }
+        } else if is_any_lose {
+            BoardState {
+                result: BoardResult::Win,
+                depth: Some(min_lose_depth.unwrap() + 1),
+                routes: Some(lose_routes),
+                // 合法手の数が3つ以上かつ理想盤面の場合にフラグを立てる
+                is_good: Some(transition_count - win_0_count >= 3 && board.is_good()),
+            }
+        } else {
+            BoardState {
+                result: BoardResult::Unknown,
+                depth: None,
+                routes: None,
+                is_good: None,
+            }
+        };
+
+        Ok((state, 打ち歩詰め_count))
+    }
+
+    // tablebase にデータベースのある駒構成の盤面の状態を読み込む (持ち駒を打てないルールで駒が取り除かれた盤面など)
+    // 解こうとしている駒構成のデータベース database_name は読み込まない。読み込んだ盤面の数を返す
+    pub fn seed(&mut self, tablebase: &mut Tablebase, database_name: &str) -> u32 {
+        let boards: Vec<Board> = self.map.iter()
+            .filter(|&(_, state)| state.result == BoardResult::Unknown)
+            .map(|(&board, _)| board)
+            .collect();
+        let mut count = 0;
+
+        for board in boards {
+            if Material::from_board(&board).get_database_name(board.get_rules()) == database_name {
+                continue;
+            }
+
+            match tablebase.probe(&board) {
+                Some(state) if state.result != BoardResult::Unknown => {
+                    if state.result == BoardResult::Win {
+                        self.wins += 1;
+                    } else {
+                        self.loses += 1;
+                    }
+
+                    self.map.insert(board, state);
+                    count += 1;
+                },
+                _ => {},
+            }
+        }
+
+        count
+    }
+
+    // 勝敗の決まる盤面がなくなるまで、深さを1ずつ増やして盤面の状態を求める
+    // 深さ d の盤面は d 回目の探索で決まり d + 1 回目から使われるので、BoardMap#seed で読み込んだ盤面も同じ回まで使わない
+    // (先に使うと最短の手数や手順の数が変わってしまう)
+    // 打ち歩詰めになるため除いた遷移の数を返す
+    pub fn solve(&mut self) -> u32 {
+        self.solve_with_progress(&mut SilentProgress)
+    }
+
+    // BoardMap#solve と同じだが、深さごとに調べた盤面の数を progress に報告する
+    pub fn solve_with_progress(&mut self, progress: &mut dyn Progress) -> u32 {
+        let max_known_depth = self.map.values().filter_map(|state| state.depth).max().unwrap_or(0);
+        let mut depth = 1;
+        let mut 打ち歩詰め_count = 0;
+
+        loop {
+            let mut current_map = BoardMap::Empty();
+            let unknown_count = self.map.values().filter(|state| state.result == BoardResult::Unknown).count();
+            let mut evaluated_count = 0;
 
-                if piece.is_promotable() {
-                    self.place_pieces(board.set_grid(x, y, Grid {piece: piece, player: 0, promoted: true}), &rest_pieces.to_vec());
-                    self.place_pieces(board.set_grid(x, y, Grid {piece: piece, player: 1, promoted: true}), &rest_pieces.to_vec());
+            progress.start(Stage::Solving(depth), Some(unknown_count));
+
+            for (&board, &state) in self.map.iter() {
+                if state.result == BoardResult::Unknown {
+                    evaluated_count += 1;
+                    progress.update(evaluated_count);
+
+                    let (new_state, count) = match self.evaluate_until(&board, depth - 1) {
+                        Ok(evaluation) => evaluation,
+                        Err(transition) => {
+                            println!("The following board was not found in map:");
+                            transition.print();
+                            println!("Transitioned from the following board:");
+                            board.print();
+                            panic!();
+                        },
+                    };
+
+                    打ち歩詰め_count += count;
+
+                    match new_state.result {
+                        BoardResult::Lose => {
+                            current_map.map.insert(board, new_state);
+                            current_map.loses += 1;
+                        },
+                        BoardResult::Win => {
+                            current_map.map.insert(board, new_state);
+                            current_map.wins += 1;
+                        },
+                        BoardResult::Unknown => {},
+                    }
                 }
             }
+
+            progress.finish();
+
+            if current_map.wins == 0 && current_map.loses == 0 && depth > max_known_depth {
+                break;
+            }
+
+            self.merge(current_map);
+
+            depth += 1;
         }
 
-        // 持ち駒にする
-        self.place_pieces(board.add_hand(0, piece, 1), &rest_pieces.to_vec());
-        self.place_pieces(board.add_hand(1, piece, 1), &rest_pieces.to_vec());
+        打ち歩詰め_count
     }
 
     pub fn merge(&mut self, board_map: BoardMap) {
@@ -133,65 +422,174 @@ impl BoardMap {
         }
     }
 
+    // BoardMap#write で書き出したデータベースを読み込む
+    // 書き出されていない深さ0の盤面を補うため、Tablebase::read_config で求めた駒構成とルールから盤面を生成し直し、
+    // データベースの盤面の状態で上書きする
+    pub fn read(path: &str) -> Result<BoardMap, String> {
+        if !Path::new(path).exists() {
+            return Err(format!("{} was not found", path));
+        }
+
+        let (config, rules) = Tablebase::read_config(path)?;
+        let pieces = config.to_pieces();
+        let hand_layout = HandLayout::from_pieces_with_capacities(&pieces, rules.get_piece_set().get_capacities())?;
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(|error| error.to_string())?;
+        let mut statement = conn.prepare("
+            SELECT board, result, depth, routes, is_good FROM boards
+        ").map_err(|error| error.to_string())?;
+        let rows = statement.query_map([], |row| {
+            Ok((
+                row.get::<_, Vec<u8>>(0)?,
+                row.get::<_, Option<u8>>(1)?,
+                row.get::<_, Option<u8>>(2)?,
+                row.get::<_, Option<u32>>(3)?,
+                row.get::<_, Option<bool>>(4)?,
+            ))
+        }).map_err(|error| error.to_string())?;
+
+        let mut board_map = BoardMap::from_pieces_with_rules(pieces, &rules);
+        let hand_layout = hand_layout.register();
+        let rules = rules.register();
+
+        for row in rows {
+            let (blob, result, depth, routes, is_good) = row.map_err(|error| error.to_string())?;
+            let result = match result {
+                Some(0) => BoardResult::Lose,
+                Some(1) => BoardResult::Win,
+                None => BoardResult::Unknown,
+                Some(result) => return Err(format!("Invalid result: {}", result)),
+            };
+
+            board_map.map.insert(Board::from_blob(&blob, hand_layout, rules), BoardState {
+                result,
+                depth,
+                routes,
+                is_good,
+            });
+        }
+
+        board_map.wins = board_map.map.values().filter(|state| state.result == BoardResult::Win).count() as u32;
+        board_map.loses = board_map.map.values().filter(|state| state.result == BoardResult::Lose).count() as u32;
+
+        Ok(board_map)
+    }
+
+    // path のデータベースを盤面の一覧で置き換える
+    // boards テーブルには深さ0以外の盤面を、meta テーブルには駒構成やルールなどを書き出す (BoardMap#write_meta)
     pub fn write(&self, path: String) {
-        let conn = Connection::open(path).unwrap();
+        self.write_with_progress(path, &mut SilentProgress);
+    }
+
+    // BoardMap#write と同じだが、書き出した盤面の数を progress に報告する
+    pub fn write_with_progress(&self, path: String, progress: &mut dyn Progress) {
+        let mut conn = Connection::open(path).unwrap();
+
+        // 書き出す盤面の一覧で置き換える
         conn.execute_batch("
-            CREATE TABLE IF NOT EXISTS boards (
+            PRAGMA journal_mode = OFF;
+            PRAGMA synchronous = OFF;
+
+            DROP TABLE IF EXISTS boards;
+            DROP TABLE IF EXISTS meta;
+
+            CREATE TABLE boards (
                 board BLOB PRIMARY KEY NOT NULL,
                 result INTEGER,
                 depth INTEGER,
                 routes INTEGER,
-                is_good INTEGER
+                is_good INTEGER,
+                sfen TEXT
             );
 
-            PRAGMA journal_mod = OFF;
-            PRAGMA synchronous = OFF;
-            BEGIN;
+            CREATE TABLE meta (
+                key TEXT PRIMARY KEY NOT NULL,
+                value TEXT NOT NULL
+            );
         ").unwrap();
 
+        self.write_meta(&conn);
+
+        let mut entries = self.map.iter();
         let mut count = 0;
-        let mut percentage = 1;
-        let total_count = self.map.len();
 
-        for (&board, &state) in self.map.iter() {
-            count += 1;
+        progress.start(Stage::Writing, Some(self.map.len()));
 
-            if percentage * total_count / 100 == count {
-                println!("BoardMap::write: {}% completed ({}/{})", percentage, count, total_count);
-                percentage += 1;
-            }
+        loop {
+            let transaction = conn.transaction().unwrap();
+            let mut batch_count = 0;
 
-            if state.depth == Some(0) {
-                continue;
+            {
+                let mut statement = transaction.prepare_cached("
+                    INSERT INTO boards (board, result, depth, routes, is_good, sfen) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                ").unwrap();
+
+                for (board, state) in entries.by_ref().take(WRITE_BATCH_SIZE) {
+                    batch_count += 1;
+                    count += 1;
+                    progress.update(count);
+
+                    if state.depth == Some(0) {
+                        continue;
+                    }
+
+                    let result = match state.result {
+                        BoardResult::Lose => Some(0),
+                        BoardResult::Win => Some(1),
+                        BoardResult::Unknown => None,
+                    };
+
+                    statement.execute(params![
+                        &board.to_blob(),
+                        &result,
+                        &state.depth,
+                        &state.routes,
+                        &state.is_good,
+                        &to_sfen(board),
+                    ]).unwrap();
+                }
             }
 
-            if state.result == BoardResult::Unknown {
-                conn.execute("
-                    INSERT OR REPLACE INTO boards (board, result, depth, routes, is_good) VALUES (?1, ?2, ?3, ?4, ?5)
-                ", params![
-                    &board.to_blob(),
-                    &Null,
-                    &Null,
-                    &Null,
-                    &state.is_good,
-                ]).unwrap();
-            } else {
-                conn.execute("
-                    INSERT OR REPLACE INTO boards (board, result, depth, routes, is_good) VALUES (?1, ?2, ?3, ?4, ?5)
-                ", params![
-                    &board.to_blob(),
-                    &(match state.result {
-                        BoardResult::Lose => 0,
-                        BoardResult::Win => 1,
-                        _ => panic!(),
-                    }),
-                    &state.depth.unwrap(),
-                    &state.routes.unwrap(),
-                    &state.is_good,
-                ]).unwrap();
+            transaction.commit().unwrap();
+
+            if batch_count < WRITE_BATCH_SIZE {
+                break;
             }
         }
 
-        conn.execute_batch("COMMIT").unwrap();
+        conn.execute_batch("
+            CREATE INDEX boards_result_depth ON boards (result, depth);
+            CREATE INDEX boards_routes ON boards (routes);
+        ").unwrap();
+
+        progress.finish();
+    }
+
+    // 駒構成やルール、書き出したバージョンと時刻を meta テーブルに書き出す
+    // 駒構成は最も駒の多い盤面のもの (持ち駒を打てないルールでは駒の少ない盤面も含まれるため)
+    fn write_meta(&self, conn: &Connection) {
+        let config = self.map.keys().map(Material::from_board).max().unwrap_or_default().get_name();
+        let rules = self.map.keys().next().map_or(&DEFAULT_RULES, |board| board.get_rules());
+        let created_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+
+        let entries = [
+            ("config", config),
+            ("rules", rules.get_name()),
+            ("width", rules.width.to_string()),
+            ("height", rules.height.to_string()),
+            ("pieces", rules.get_piece_set().name.to_string()),
+            ("promotion_rows", rules.promotion_rows.to_string()),
+            ("forced_promotion_rows", rules.forced_promotion_rows.iter().map(|rows| rows.to_string()).collect::<Vec<_>>().concat()),
+            ("allow_non_promotion", rules.allow_non_promotion.to_string()),
+            ("allow_drops", rules.allow_drops.to_string()),
+            ("stalemate", format!("{:?}", rules.stalemate).to_lowercase()),
+            ("version", env!("CARGO_PKG_VERSION").to_string()),
+            ("created_at", created_at.to_string()),
+        ];
+
+        let mut statement = conn.prepare("INSERT INTO meta (key, value) VALUES (?1, ?2)").unwrap();
+
+        for (key, value) in entries.iter() {
+            statement.execute(params![key, value]).unwrap();
+        }
     }
 }
@@ -1,4 +1,5 @@
 use ::Piece::Piece;
+use ::PieceSet::STANDARD_PIECE_SET;
 
 // 移動量
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -7,31 +8,109 @@ pub struct Move {
     pub y: i8,
 }
 
-const M00: Move = Move {x: -2, y: -2};
-const M01: Move = Move {x: -1, y: -2};
-const M02: Move = Move {x:  0, y: -2};
-const M03: Move = Move {x:  1, y: -2};
-const M04: Move = Move {x:  2, y: -2};
-// const M10: Move = Move {x: -2, y: -1};
-const M11: Move = Move {x: -1, y: -1};
-const M12: Move = Move {x:  0, y: -1};
-const M13: Move = Move {x:  1, y: -1};
-// const M14: Move = Move {x:  2, y: -1};
-const M20: Move = Move {x: -2, y:  0};
-const M21: Move = Move {x: -1, y:  0};
+pub const M01: Move = Move {x: -1, y: -2};
+pub const M03: Move = Move {x:  1, y: -2};
+pub const M11: Move = Move {x: -1, y: -1};
+pub const M12: Move = Move {x:  0, y: -1};
+pub const M13: Move = Move {x:  1, y: -1};
+pub const M21: Move = Move {x: -1, y:  0};
 // M22 will never used :)
-const M23: Move = Move {x:  1, y:  0};
-const M24: Move = Move {x:  2, y:  0};
-// const M30: Move = Move {x: -2, y:  1};
-const M31: Move = Move {x: -1, y:  1};
-const M32: Move = Move {x:  0, y:  1};
-const M33: Move = Move {x:  1, y:  1};
-// const M34: Move = Move {x:  2, y:  1};
-const M40: Move = Move {x: -2, y:  2};
-// const M41: Move = Move {x: -1, y:  2};
-const M42: Move = Move {x:  0, y:  2};
-// const M43: Move = Move {x:  1, y:  2};
-const M44: Move = Move {x:  2, y:  2};
+pub const M23: Move = Move {x:  1, y:  0};
+pub const M31: Move = Move {x: -1, y:  1};
+pub const M32: Move = Move {x:  0, y:  1};
+pub const M33: Move = Move {x:  1, y:  1};
+
+// 走り駒の方向 (先手から見た向き)
+// 後手の駒の方向は 7 - インデックス で求まり、4 以上の方向はマスの番号が増える向きになる
+pub const DIRECTIONS: [Move; 8] = [M11, M12, M13, M21, M23, M31, M32, M33];
+
+pub const UP_LEFT: u8 = 1 << 0;
+pub const UP: u8 = 1 << 1;
+pub const UP_RIGHT: u8 = 1 << 2;
+pub const LEFT: u8 = 1 << 3;
+pub const RIGHT: u8 = 1 << 4;
+pub const DOWN_LEFT: u8 = 1 << 5;
+pub const DOWN: u8 = 1 << 6;
+pub const DOWN_RIGHT: u8 = 1 << 7;
+
+// 盤の一辺の最大のマス数
+pub const MAX_BOARD_SIZE: usize = 5;
+// 盤のマスの最大数
+pub const MAX_SQUARES: usize = MAX_BOARD_SIZE * MAX_BOARD_SIZE;
+
+// Grid#to_i の値とマスの番号 y * 幅 + x ごとの1マスずつの移動先の一覧 (マスの番号のビットで表す)
+pub type AttackTable = [[u32; MAX_SQUARES]; 29];
+// マスの番号と DIRECTIONS のインデックスごとの、盤の端までの走り駒の移動先の一覧
+pub type RayTable = [[u32; 8]; MAX_SQUARES];
+
+// 走り駒の移動先の一覧を盤の大きさ (幅 - 1, 高さ - 1) ごとに作る
+const fn build_rays() -> [[RayTable; MAX_BOARD_SIZE]; MAX_BOARD_SIZE] {
+    let mut tables = [[[[0u32; 8]; MAX_SQUARES]; MAX_BOARD_SIZE]; MAX_BOARD_SIZE];
+    let mut width = 1;
+
+    while width <= MAX_BOARD_SIZE {
+        let mut height = 1;
+
+        while height <= MAX_BOARD_SIZE {
+            let table = &mut tables[width - 1][height - 1];
+            let mut square = 0;
+
+            while square < width * height {
+                let mut direction = 0;
+
+                while direction < 8 {
+                    let mut x = (square % width) as i8 + DIRECTIONS[direction].x;
+                    let mut y = (square / width) as i8 + DIRECTIONS[direction].y;
+
+                    while x >= 0 && x < width as i8 && y >= 0 && y < height as i8 {
+                        table[square][direction] |= 1 << (y as usize * width + x as usize);
+                        x += DIRECTIONS[direction].x;
+                        y += DIRECTIONS[direction].y;
+                    }
+
+                    direction += 1;
+                }
+
+                square += 1;
+            }
+
+            height += 1;
+        }
+
+        width += 1;
+    }
+
+    tables
+}
+
+// 飛車・角行・香車などが盤の端まで走る移動先
+pub static RAYS: [[RayTable; MAX_BOARD_SIZE]; MAX_BOARD_SIZE] = build_rays();
+
+// square から directions (DIRECTIONS のインデックスのビット) の方向に走る駒の移動先
+// 駒のあるマス (occupied) では止まり、そのマスは移動先に含める
+pub fn get_slide_attacks(rays: &RayTable, square: usize, directions: u8, occupied: u32) -> u32 {
+    let mut attacks = 0;
+    let mut directions = directions;
+
+    while directions != 0 {
+        let direction = directions.trailing_zeros() as usize;
+        directions &= directions - 1;
+
+        let ray = rays[square][direction];
+        let blockers = ray & occupied;
+
+        if blockers == 0 {
+            attacks |= ray;
+            continue;
+        }
+
+        // 最も近い駒は、マスの番号が増える向きなら最下位の、減る向きなら最上位のビット
+        let blocker = if direction >= 4 {blockers.trailing_zeros()} else {31 - blockers.leading_zeros()};
+        attacks |= ray & !rays[blocker as usize][direction];
+    }
+
+    attacks
+}
 
 // マス
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -133,170 +212,13 @@ impl Grid {
         }
     }
 
-    fn get_raw_moves(&self) -> Vec<Move> {
-        /*
-           ○○○
-           ○金○
-             ○
-         */
-        let 金将Moves = vec![
-                 M11, M12, M13,
-                 M21,      M23,
-                      M32,
-        ];
-
-        match self.piece {
-            Piece::Empty => vec![],
-            Piece::王将 => {
-                assert!(self.promoted == false);
-                /*
-                   ○○○
-                   ○王○
-                   ○○○
-                 */
-                vec![
-                         M11, M12, M13,
-                         M21,      M23,
-                         M31, M32, M33,
-                ]
-            },
-            Piece::飛車 => {
-                if !self.promoted {
-                    /*
-                         ○
-                         ○
-                     ○○飛○○
-                         ○
-                         ○
-                     */
-                    vec![
-                                  M02,
-                                  M12,
-                        M20, M21,      M23, M24,
-                                  M32,
-                                  M42,
-                    ]
-                } else {
-                    /*
-                         ○
-                       ○○○
-                     ○○龍○○
-                       ○○○
-                         ○
-                     */
-                    vec![
-                                  M02,
-                             M11, M12, M13,
-                        M20, M21,      M23, M24,
-                             M31, M32, M33,
-                                  M42,
-                    ]
-                }
-            },
-            Piece::角行 => {
-                if !self.promoted {
-                    /*
-                     ○      ○
-                       ○  ○
-                         角
-                       ○  ○
-                     ○      ○
-                     */
-                    vec![
-                        M00,                M04,
-                             M11,      M13,
-
-                             M31,      M33,
-                        M40,                M44,
-                    ]
-                } else {
-                    /*
-                     ○      ○
-                       ○○○
-                       ○馬○
-                       ○○○
-                     ○      ○
-                     */
-                    vec![
-                        M00,                M04,
-                             M11, M12, M13,
-                             M21,      M23,
-                             M31, M32, M33,
-                        M40,                M44,
-                    ]
-                }
-            },
-            Piece::金将 => {
-                assert!(self.promoted == false);
-                金将Moves
-            },
-            Piece::銀将 => {
-                if !self.promoted {
-                    /*
-                       ○○○
-                         銀
-                       ○  ○
-                     */
-                    vec![
-                             M11, M12, M13,
-
-                             M31,      M33,
-                    ]
-                } else {
-                    金将Moves
-                }
-            },
-            Piece::桂馬 => {
-                if !self.promoted {
-                    /*
-                       ○  ○
-
-                         桂
-                     */
-                    vec![
-                             M01,      M03,
-                    ]
-                } else {
-                    金将Moves
-                }
-            },
-            Piece::香車 => {
-                if !self.promoted {
-                    /*
-                         ○
-                         ○
-                         香
-                     */
-                    vec![
-                                  M02,
-                                  M12,
-                    ]
-                } else {
-                    金将Moves
-                }
-            },
-            Piece::歩兵 => {
-                if !self.promoted {
-                    /*
-                         ○
-                         歩
-                     */
-                    vec![
-                                  M12,
-                    ]
-                } else {
-                    金将Moves
-                }
-            },
-        }
+    // 標準の駒の1マスずつの移動量 (走り駒の方向は Grid#get_slides で求める)
+    pub fn get_moves(&self) -> Vec<Move> {
+        STANDARD_PIECE_SET.get_moves(self)
     }
 
-    pub fn get_moves(&self) -> Vec<Move> {
-        let raw_moves = self.get_raw_moves();
-        if self.player == 0 {
-            raw_moves
-        } else {
-            raw_moves.iter().map(|&m| Move {x: -m.x, y: -m.y}).collect::<Vec<_>>()
-        }
+    // 標準の駒の盤の端か他の駒まで走る方向
+    pub fn get_slides(&self) -> Vec<Move> {
+        STANDARD_PIECE_SET.get_slides(self)
     }
 }
@@ -0,0 +1,163 @@
+use std::cmp::Ordering;
+use std::fmt;
+use ::Board::Board;
+use ::Piece::Piece;
+use ::PieceSet::PieceSet;
+use ::Rules::Rules;
+use ::Tablebase::Tablebase;
+
+// 王将だけの駒構成の名前 (ファイル名が "." で始まる隠しファイルにならないようにする)
+pub const KINGS_ONLY_NAME: &str = "K";
+
+// 王将以外の駒の構成 (盤上の駒は成る前の駒として数え、両者の持ち駒も含める)
+// 持ち駒の番号ごとの枚数で表すので、駒の並べ方によらず同じ駒構成は同じ値になる
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct Material {
+    counts: [u8; 7],
+}
+
+impl Material {
+    // 王将と空きマスは数えない
+    // 枚数は 255 枚で頭打ちにする (HandLayout::from_pieces_with_capacities などで表せない駒構成になる)
+    pub fn from_pieces(pieces: &[Piece]) -> Material {
+        let mut counts: [u8; 7] = [0; 7];
+
+        for piece in pieces.iter().filter(|&&piece| piece != Piece::Empty && piece != Piece::王将) {
+            let count = &mut counts[piece.to_hand_index() as usize];
+            *count = count.saturating_add(1);
+        }
+
+        Material { counts }
+    }
+
+    pub fn from_board(board: &Board) -> Material {
+        let mut counts: [u8; 7] = [0; 7];
+        let (width, height) = board.get_size();
+
+        for y in 0..height {
+            for x in 0..width {
+                let grid = board.get_grid(x, y);
+
+                if grid.piece != Piece::Empty && grid.piece != Piece::王将 {
+                    let count = &mut counts[grid.piece.to_hand_index() as usize];
+                    *count = count.saturating_add(1);
+                }
+            }
+        }
+
+        for (i, count) in counts.iter_mut().enumerate() {
+            let piece = Piece::from_hand_index(i);
+            *count = count.saturating_add(board.get_hand_count(0, piece)).saturating_add(board.get_hand_count(1, piece));
+        }
+
+        Material { counts }
+    }
+
+    // 持ち駒の番号の数字の並び (Material#get_name の形式) から駒構成を求める
+    // 王将だけの駒構成は KINGS_ONLY_NAME のほか、以前の名前の空文字列でもよい
+    pub fn from_name(name: &str) -> Result<Material, String> {
+        let mut counts: [u8; 7] = [0; 7];
+
+        if name == KINGS_ONLY_NAME {
+            return Ok(Material { counts });
+        }
+
+        for letter in name.chars() {
+            let index = match letter.to_digit(10) {
+                Some(digit) if digit < 7 => digit as usize,
+                _ => return Err(format!("Invalid config {}: Unknown piece {}", name, letter)),
+            };
+
+            counts[index] = counts[index].checked_add(1).ok_or_else(|| format!("Invalid config {}: Too many {}", name, Piece::from_hand_index(index).to_char()))?;
+        }
+
+        Ok(Material { counts })
+    }
+
+    // 駒の名前 (piece_set での "金" などの PieceSet#get_name の表記、または "G" などの USI の表記) の一覧から駒構成を求める
+    pub fn from_piece_names<S: AsRef<str>>(names: &[S], piece_set: &PieceSet) -> Result<Material, String> {
+        let pieces: Vec<Piece> = names.iter().map(|name| {
+            let name = name.as_ref();
+            let mut letters = name.chars();
+
+            let piece = match (letters.next(), letters.next()) {
+                (Some(letter), None) => {
+                    (0..7).map(Piece::from_hand_index).find(|&piece| piece_set.get_name(piece, false) == letter).or_else(|| piece_set.from_usi_char(letter))
+                },
+                _ => None,
+            };
+
+            match piece {
+                Some(Piece::王将) | None => Err(format!("Invalid config: Unknown piece {}", name)),
+                Some(piece) => Ok(piece),
+            }
+        }).collect::<Result<_, String>>()?;
+
+        Ok(Material::from_pieces(&pieces))
+    }
+
+    pub fn get_count(&self, piece: Piece) -> u8 {
+        self.counts[piece.to_hand_index() as usize]
+    }
+
+    // 王将以外の駒の枚数
+    pub fn len(&self) -> usize {
+        self.counts.iter().map(|&count| count as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // other のすべての駒を含む駒構成か (持ち駒を打てないルールで駒を取った後の駒構成など)
+    pub fn contains(&self, other: &Material) -> bool {
+        self.counts.iter().zip(other.counts.iter()).all(|(count, other_count)| count >= other_count)
+    }
+
+    // 駒を持ち駒の番号順に並べたもの
+    pub fn to_pieces(&self) -> Vec<Piece> {
+        self.counts.iter().enumerate().flat_map(|(i, &count)| {
+            (0..count).map(move |_| Piece::from_hand_index(i))
+        }).collect()
+    }
+
+    // 駒を持ち駒の番号順に並べ、piece_set での名前 (PieceSet#get_name) で表したもの
+    pub fn get_piece_names(&self, piece_set: &PieceSet) -> Vec<String> {
+        self.to_pieces().iter().map(|&piece| piece_set.get_name(piece, false).to_string()).collect()
+    }
+
+    // 駒の持ち駒の番号を順に並べた数字の並び (データベースのファイル名などに使う)
+    // 王将だけの駒構成では KINGS_ONLY_NAME になる
+    pub fn get_name(&self) -> String {
+        if self.is_empty() {
+            return KINGS_ONLY_NAME.to_string();
+        }
+
+        self.to_pieces().iter().map(|piece| piece.to_hand_index().to_string()).collect::<Vec<_>>().concat()
+    }
+
+    // rules で解いた駒構成のデータベースのファイル名 (拡張子を除く)
+    pub fn get_database_name(&self, rules: &Rules) -> String {
+        Tablebase::get_database_name(&self.get_name(), rules)
+    }
+}
+
+impl fmt::Display for Material {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.get_name())
+    }
+}
+
+// 駒の少ない駒構成から順に、同じ枚数では持ち駒の番号の並びの辞書順に並べる (Tablebase::enumerate_configs の順番)
+impl Ord for Material {
+    fn cmp(&self, other: &Material) -> Ordering {
+        let indices = |material: &Material| material.to_pieces().iter().map(|piece| piece.to_hand_index()).collect::<Vec<_>>();
+        self.len().cmp(&other.len()).then_with(|| indices(self).cmp(&indices(other)))
+    }
+}
+
+impl PartialOrd for Material {
+    fn partial_cmp(&self, other: &Material) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
@@ -0,0 +1,94 @@
+use std::ops::AddAssign;
+use ::Board::{Action, Board};
+use ::Piece::Piece;
+
+// 指定した手数で到達する末端の盤面の数を、最後の一手の種類ごとに数えたもの
+// 遷移は Board#get_possible_transitions と同じく、王を取る手以外のすべての手を数える
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct PerftCount {
+    pub nodes: u64,
+    // 盤上の駒を動かす手
+    pub moves: u64,
+    // 相手の駒を取る手
+    pub captures: u64,
+    // 成る手 (もともと成っている駒の移動は含まない)
+    pub promotions: u64,
+    // 打ち駒
+    pub drops: u64,
+}
+
+impl AddAssign for PerftCount {
+    fn add_assign(&mut self, other: PerftCount) {
+        self.nodes += other.nodes;
+        self.moves += other.moves;
+        self.captures += other.captures;
+        self.promotions += other.promotions;
+        self.drops += other.drops;
+    }
+}
+
+// 王を取る手を除いたすべての手
+fn get_actions(board: &Board) -> Vec<Action> {
+    let mut actions: Vec<Action> = board.get_possible_moves().into_iter().filter(|mov| {
+        board.get_grid(mov.to.x, mov.to.y).piece != Piece::王将
+    }).map(Action::Move).collect();
+
+    actions.extend(board.get_possible_drops().into_iter().map(Action::Drop));
+    actions
+}
+
+// 一手指した後、残り depth - 1 手で到達する末端の盤面の数
+fn perft_action(board: &Board, action: &Action, depth: u8) -> PerftCount {
+    if depth > 1 {
+        return perft(&board.apply_action(action), depth - 1);
+    }
+
+    let mut count = PerftCount {nodes: 1, ..PerftCount::default()};
+
+    match action {
+        Action::Move(mov) => {
+            count.moves = 1;
+
+            if board.get_grid(mov.to.x, mov.to.y).piece != Piece::Empty {
+                count.captures = 1;
+            }
+
+            if mov.promote && !board.get_grid(mov.from.x, mov.from.y).promoted {
+                count.promotions = 1;
+            }
+        },
+        Action::Drop(_) => {
+            count.drops = 1;
+        },
+    }
+
+    count
+}
+
+// board は手番つきの盤面 (手番側が先手になるように反転して数える)
+pub fn perft(board: &Board, depth: u8) -> PerftCount {
+    if depth == 0 {
+        return PerftCount {nodes: 1, ..PerftCount::default()};
+    }
+
+    let board = &board.normalize();
+    let mut count = PerftCount::default();
+
+    for action in get_actions(board) {
+        count += perft_action(board, &action, depth);
+    }
+
+    count
+}
+
+// 最初の一手 (Board#legal_moves と同じく先手から見た座標) ごとの末端の盤面の数 (いわゆる divide)
+pub fn divide(board: &Board, depth: u8) -> Vec<(Action, PerftCount)> {
+    assert!(depth >= 1);
+
+    let normalized = board.normalize();
+
+    get_actions(&normalized).into_iter().map(|action| {
+        let count = perft_action(&normalized, &action, depth);
+        (board.normalize_action(action), count)
+    }).collect()
+}
@@ -58,4 +58,33 @@ impl Piece {
             _ => panic!(),
         }
     }
+
+    // USIプロトコルでの駒の表記 (先手の駒として大文字で表す)
+    pub fn to_usi_char(&self) -> char {
+        match *self {
+            Piece::王将 => 'K',
+            Piece::飛車 => 'R',
+            Piece::角行 => 'B',
+            Piece::金将 => 'G',
+            Piece::銀将 => 'S',
+            Piece::桂馬 => 'N',
+            Piece::香車 => 'L',
+            Piece::歩兵 => 'P',
+            Piece::Empty => panic!(),
+        }
+    }
+
+    pub fn from_usi_char(letter: char) -> Option<Piece> {
+        match letter.to_ascii_uppercase() {
+            'K' => Some(Piece::王将),
+            'R' => Some(Piece::飛車),
+            'B' => Some(Piece::角行),
+            'G' => Some(Piece::金将),
+            'S' => Some(Piece::銀将),
+            'N' => Some(Piece::桂馬),
+            'L' => Some(Piece::香車),
+            'P' => Some(Piece::歩兵),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,560 @@
+use std::fs;
+use std::sync::OnceLock;
+use ::Grid::{AttackTable, Grid, Move, DIRECTIONS, MAX_BOARD_SIZE, MAX_SQUARES};
+use ::Grid::{M01, M03, M11, M12, M13, M21, M23, M31, M32, M33};
+use ::Grid::{DOWN, DOWN_LEFT, DOWN_RIGHT, LEFT, RIGHT, UP, UP_LEFT, UP_RIGHT};
+use ::Piece::Piece;
+use ::util::{Registry, DEFAULT_HAND_CAPACITIES, MAX_HAND_CAPACITY};
+
+// 駒の定義ファイル (<名前>.txt) を置くディレクトリ
+pub const PIECES_DIRECTORY: &str = "pieces";
+
+// 1マスずつの移動量の x, y の絶対値の最大
+const MAX_STEP: i8 = 2;
+// 1マスずつの移動先を表すビットの一辺の数
+const STEP_SIZE: i8 = MAX_STEP * 2 + 1;
+
+// 移動量 (x, y) を表すビットの番号
+const fn get_step_index(piece_move: Move) -> u32 {
+    ((piece_move.y + MAX_STEP) * STEP_SIZE + piece_move.x + MAX_STEP) as u32
+}
+
+// 駒の動き (先手から見た向き)
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Movement {
+    // 1マスずつの移動量 (x, y) (-2 以上 2 以下) を get_step_index 番目のビットで表したもの
+    pub steps: u32,
+    // 盤の端か他の駒まで走る方向 (DIRECTIONS のインデックスのビット)
+    pub slides: u8,
+}
+
+impl Movement {
+    pub const fn new(moves: &[Move], slides: u8) -> Movement {
+        let mut steps = 0;
+        let mut i = 0;
+
+        while i < moves.len() {
+            steps |= 1 << get_step_index(moves[i]);
+            i += 1;
+        }
+
+        Movement {steps, slides}
+    }
+
+    pub fn get_moves(&self) -> Vec<Move> {
+        (0..STEP_SIZE * STEP_SIZE)
+            .filter(|&index| self.steps & (1 << index) != 0)
+            .map(|index| Move {x: index % STEP_SIZE - MAX_STEP, y: index / STEP_SIZE - MAX_STEP})
+            .collect()
+    }
+}
+
+// 持ち駒になる駒の定義
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct PieceDefinition {
+    // 盤面の表示に使う1文字
+    pub name: char,
+    // USIプロトコルでの表記 (先手の駒として大文字で表す)
+    pub usi: char,
+    pub movement: Movement,
+    // 成った後の表示名と動き (成れない駒は None)
+    pub promotion: Option<(char, Movement)>,
+    // 持ち駒の最大枚数の既定値 (HandLayout の既定の最大枚数)
+    pub capacity: u8,
+    // 成っていない駒が存在できない段の数の既定値 (Rules#forced_promotion_rows の既定値)
+    pub forced_promotion_rows: u8,
+}
+
+/*
+   ○○○
+   ○王○
+   ○○○
+ */
+const 王将_MOVEMENT: Movement = Movement::new(&[
+         M11, M12, M13,
+         M21,      M23,
+         M31, M32, M33,
+], 0);
+
+/*
+   ○○○
+   ○金○
+     ○
+ */
+const 金将_MOVEMENT: Movement = Movement::new(&[
+         M11, M12, M13,
+         M21,      M23,
+              M32,
+], 0);
+
+const ROOK_SLIDES: u8 = UP | LEFT | RIGHT | DOWN;
+const BISHOP_SLIDES: u8 = UP_LEFT | UP_RIGHT | DOWN_LEFT | DOWN_RIGHT;
+
+// 駒の種類ごとの定義 (持ち駒の番号順に並べる)
+// 王将は定義できず、常に周囲1マスに動ける
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct PieceSet {
+    // Rules の表記に使う名前 (標準の駒は空文字列)
+    pub name: &'static str,
+    pub pieces: [PieceDefinition; 7],
+}
+
+// 本将棋の駒
+pub const STANDARD_PIECE_SET: PieceSet = PieceSet {
+    name: "",
+    pieces: [
+        PieceDefinition {
+            name: '飛',
+            usi: 'R',
+            movement: Movement::new(&[], ROOK_SLIDES),
+            /*
+               ○  ○
+                 龍
+               ○  ○
+             */
+            promotion: Some(('龍', Movement::new(&[
+                     M11,      M13,
+
+                     M31,      M33,
+            ], ROOK_SLIDES))),
+            capacity: DEFAULT_HAND_CAPACITIES[0],
+            forced_promotion_rows: 0,
+        },
+        PieceDefinition {
+            name: '角',
+            usi: 'B',
+            movement: Movement::new(&[], BISHOP_SLIDES),
+            /*
+                 ○
+               ○馬○
+                 ○
+             */
+            promotion: Some(('馬', Movement::new(&[
+                          M12,
+                     M21,      M23,
+                          M32,
+            ], BISHOP_SLIDES))),
+            capacity: DEFAULT_HAND_CAPACITIES[1],
+            forced_promotion_rows: 0,
+        },
+        PieceDefinition {
+            name: '金',
+            usi: 'G',
+            movement: 金将_MOVEMENT,
+            promotion: None,
+            capacity: DEFAULT_HAND_CAPACITIES[2],
+            forced_promotion_rows: 0,
+        },
+        PieceDefinition {
+            name: '銀',
+            usi: 'S',
+            /*
+               ○○○
+                 銀
+               ○  ○
+             */
+            movement: Movement::new(&[
+                     M11, M12, M13,
+
+                     M31,      M33,
+            ], 0),
+            promotion: Some(('全', 金将_MOVEMENT)),
+            capacity: DEFAULT_HAND_CAPACITIES[3],
+            forced_promotion_rows: 0,
+        },
+        PieceDefinition {
+            name: '桂',
+            usi: 'N',
+            /*
+               ○  ○
+
+                 桂
+             */
+            movement: Movement::new(&[
+                     M01,      M03,
+            ], 0),
+            promotion: Some(('圭', 金将_MOVEMENT)),
+            capacity: DEFAULT_HAND_CAPACITIES[4],
+            forced_promotion_rows: 2,
+        },
+        PieceDefinition {
+            name: '香',
+            usi: 'L',
+            movement: Movement::new(&[], UP),
+            promotion: Some(('杏', 金将_MOVEMENT)),
+            capacity: DEFAULT_HAND_CAPACITIES[5],
+            forced_promotion_rows: 1,
+        },
+        PieceDefinition {
+            name: '歩',
+            usi: 'P',
+            /*
+                 ○
+                 歩
+             */
+            movement: Movement::new(&[
+                          M12,
+            ], 0),
+            promotion: Some(('と', 金将_MOVEMENT)),
+            capacity: DEFAULT_HAND_CAPACITIES[6],
+            forced_promotion_rows: 1,
+        },
+    ],
+};
+
+// 駒の定義から作る移動先の表
+pub struct PieceTables {
+    // Grid#to_i の値ごとの走る方向 (DIRECTIONS のインデックスのビット)
+    pub slides: [u8; 29],
+    // 盤の大きさ (幅 - 1, 高さ - 1) ごとの1マスずつの移動先
+    pub step_attacks: [[AttackTable; MAX_BOARD_SIZE]; MAX_BOARD_SIZE],
+}
+
+// PieceSet#register で登録した標準以外の PieceSet
+static PIECE_SETS: Registry<PieceSet> = Registry::new();
+// PieceSet#register のインデックスごとの移動先の表 (最初に使うときに作る)
+static PIECE_TABLES: [OnceLock<&'static PieceTables>; 256] = [const { OnceLock::new() }; 256];
+
+// 定義ファイルでの走る方向の表記 (DIRECTIONS の順)
+const DIRECTION_NAMES: [&str; 8] = ["NW", "N", "NE", "W", "E", "SW", "S", "SE"];
+
+fn parse_char(key: &str, value: &str) -> Result<char, String> {
+    let mut letters = value.chars();
+
+    match (letters.next(), letters.next()) {
+        (Some(letter), None) => Ok(letter),
+        _ => Err(format!("{} must be a single character: {}", key, value)),
+    }
+}
+
+// "-1,-1 0,-1 1,-1" のような空白区切りの移動量 (x は右、y は下 (自陣の方向) が正)
+fn parse_steps(value: &str) -> Result<u32, String> {
+    let mut steps = 0;
+
+    for item in value.split_whitespace() {
+        let offset: Vec<i8> = item.split(',').filter_map(|number| number.trim().parse().ok()).collect();
+
+        if offset.len() != 2 || item.split(',').count() != 2 || offset.iter().any(|number| number.abs() > MAX_STEP) || offset == [0, 0] {
+            return Err(format!("Invalid move (x and y must be between -{} and {}): {}", MAX_STEP, MAX_STEP, item));
+        }
+
+        steps |= 1 << get_step_index(Move {x: offset[0], y: offset[1]});
+    }
+
+    Ok(steps)
+}
+
+// "N E S W" のような空白区切りの方向 (N が敵陣の方向)
+fn parse_slides(value: &str) -> Result<u8, String> {
+    let mut slides = 0;
+
+    for item in value.split_whitespace() {
+        match DIRECTION_NAMES.iter().position(|&name| name == item) {
+            Some(direction) => slides |= 1 << direction,
+            None => return Err(format!("Invalid direction: {}", item)),
+        }
+    }
+
+    Ok(slides)
+}
+
+impl PieceSet {
+    // 駒の定義を読み込む
+    // "[駒の名前]" (銀将 や S のような標準の駒の名前かUSIの表記) で置き換える持ち駒の種類を選び、
+    // 続く "キー = 値" の行で定義する ("#" 以降はコメント)
+    // * name, usi: 表示名とUSIの表記 (省略すると置き換える駒のもの)
+    // * moves, slides: 1マスずつの移動量と走る方向 (省略すると動けない)
+    // * promoted_name, promoted_moves, promoted_slides: 成った後の表示名と動き (promoted_name がなければ成れない)
+    // * capacity: 持ち駒の最大枚数の既定値 (省略すると置き換える駒のもの)
+    // * forced_promotion_rows: 成っていない駒が存在できない段の数の既定値 (省略すると 0)
+    // 定義しなかった種類は標準の駒のままになる
+    pub fn parse(name: &str, text: &str) -> Result<PieceSet, String> {
+        PieceSet::validate_name(name)?;
+
+        // 同じ名前の PieceSet を登録済みならその名前を使い、読み込むたびに名前の文字列を確保し直さない
+        let static_name = match PIECE_SETS.find(|piece_set| piece_set.name == name) {
+            Some(piece_set) => piece_set.name,
+            None => Box::leak(name.to_string().into_boxed_str()),
+        };
+        let mut piece_set = PieceSet {
+            name: static_name,
+            ..STANDARD_PIECE_SET
+        };
+        let mut defined = [false; 7];
+        let mut promoted_names: [Option<char>; 7] = [None; 7];
+        let mut current: Option<usize> = None;
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                let header = line[1..line.len() - 1].trim();
+                let index = (0..7).find(|&i| {
+                    let piece = Piece::from_hand_index(i);
+                    header == format!("{:?}", piece) || header == piece.to_usi_char().to_string()
+                }).ok_or_else(|| format!("Unknown piece at line {}: {}", number + 1, header))?;
+
+                if defined[index] {
+                    return Err(format!("Piece defined twice at line {}: {}", number + 1, header));
+                }
+
+                // 名前と持ち駒の最大枚数のほかは置き換える駒から引き継がない
+                let definition = &mut piece_set.pieces[index];
+                definition.movement = Movement {steps: 0, slides: 0};
+                definition.promotion = None;
+                definition.forced_promotion_rows = 0;
+
+                defined[index] = true;
+                current = Some(index);
+                continue;
+            }
+
+            let (key, value) = match line.find('=') {
+                Some(position) => (line[..position].trim(), line[position + 1..].trim()),
+                None => return Err(format!("Invalid line {}: {}", number + 1, line)),
+            };
+            let index = current.ok_or_else(|| format!("Line {} is outside of a piece definition: {}", number + 1, line))?;
+            let definition = &mut piece_set.pieces[index];
+            let promoted_movement = definition.promotion.map(|(_, movement)| movement).unwrap_or(Movement {steps: 0, slides: 0});
+
+            match key {
+                "name" => definition.name = parse_char(key, value)?,
+                "usi" => definition.usi = parse_char(key, value)?.to_ascii_uppercase(),
+                "moves" => definition.movement.steps = parse_steps(value)?,
+                "slides" => definition.movement.slides = parse_slides(value)?,
+                "promoted_name" => promoted_names[index] = Some(parse_char(key, value)?),
+                "promoted_moves" => definition.promotion = Some((' ', Movement {steps: parse_steps(value)?, ..promoted_movement})),
+                "promoted_slides" => definition.promotion = Some((' ', Movement {slides: parse_slides(value)?, ..promoted_movement})),
+                "capacity" => definition.capacity = value.parse().map_err(|_| format!("Invalid capacity: {}", value))?,
+                "forced_promotion_rows" => definition.forced_promotion_rows = value.parse().map_err(|_| format!("Invalid forced promotion rows: {}", value))?,
+                _ => return Err(format!("Unknown key at line {}: {}", number + 1, key)),
+            }
+        }
+
+        for index in (0..7).filter(|&index| defined[index]) {
+            let definition = &mut piece_set.pieces[index];
+
+            match (promoted_names[index], definition.promotion) {
+                (Some(name), promotion) => {
+                    definition.promotion = Some((name, promotion.map(|(_, movement)| movement).unwrap_or(Movement {steps: 0, slides: 0})));
+                },
+                (None, Some(_)) => {
+                    return Err(format!("promoted_name of {:?} is missing", Piece::from_hand_index(index)));
+                },
+                (None, None) => {},
+            }
+        }
+
+        piece_set.validate()?;
+        Ok(piece_set)
+    }
+
+    // PIECES_DIRECTORY/<name>.txt から駒の定義を読み込む
+    // name はファイル名に使うので、ファイルを読む前に確かめる (pieces ディレクトリの外のファイルを読まないように)
+    pub fn load(name: &str) -> Result<PieceSet, String> {
+        PieceSet::validate_name(name)?;
+
+        let path = format!("{}/{}.txt", PIECES_DIRECTORY, name);
+        let text = fs::read_to_string(&path).map_err(|error| format!("Cannot read {}: {}", path, error))?;
+        PieceSet::parse(name, &text)
+    }
+
+    // Rules の表記やファイル名に使える名前か (英数字と "_" だけからなる)
+    fn validate_name(name: &str) -> Result<(), String> {
+        if !name.chars().all(|letter| letter.is_ascii_alphanumeric() || letter == '_') {
+            return Err(format!("Piece set name must consist of letters, digits and underscores: {}", name));
+        }
+
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        PieceSet::validate_name(self.name)?;
+
+        let center = 1 << get_step_index(Move {x: 0, y: 0});
+
+        for (i, definition) in self.pieces.iter().enumerate() {
+            let piece = Piece::from_hand_index(i);
+
+            if !definition.usi.is_ascii_uppercase() || definition.usi == 'K' || self.pieces[..i].iter().any(|other| other.usi == definition.usi) {
+                return Err(format!("Invalid USI letter of {:?}: {}", piece, definition.usi));
+            }
+
+            let movements = [Some(definition.movement), definition.promotion.map(|(_, movement)| movement)];
+
+            if movements.iter().flatten().any(|movement| movement.steps >> (STEP_SIZE * STEP_SIZE) != 0 || movement.steps & center != 0) {
+                return Err(format!("Invalid moves of {:?}", piece));
+            }
+
+            // Grid#to_i には成った金将を表す値がない
+            if piece == Piece::金将 && definition.promotion.is_some() {
+                return Err(format!("The piece in place of {:?} cannot promote", piece));
+            }
+
+            if definition.capacity > MAX_HAND_CAPACITY {
+                return Err(format!("Capacity of {:?} must be at most {}: {}", piece, MAX_HAND_CAPACITY, definition.capacity));
+            }
+
+            if definition.forced_promotion_rows as usize > MAX_BOARD_SIZE || (definition.forced_promotion_rows > 0 && definition.promotion.is_none()) {
+                return Err(format!("Invalid forced promotion rows of {:?}: {}", piece, definition.forced_promotion_rows));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get_definition(&self, piece: Piece) -> &PieceDefinition {
+        &self.pieces[piece.to_hand_index() as usize]
+    }
+
+    pub fn is_promotable(&self, piece: Piece) -> bool {
+        match piece {
+            Piece::Empty | Piece::王将 => false,
+            _ => self.get_definition(piece).promotion.is_some(),
+        }
+    }
+
+    // 先手から見た grid の駒の動き
+    pub fn get_movement(&self, grid: &Grid) -> Movement {
+        match grid.piece {
+            Piece::Empty => Movement {steps: 0, slides: 0},
+            Piece::王将 => {
+                assert!(!grid.promoted);
+                王将_MOVEMENT
+            },
+            piece if grid.promoted => self.get_definition(piece).promotion.expect("The piece cannot promote").1,
+            piece => self.get_definition(piece).movement,
+        }
+    }
+
+    // 1マスずつの移動量 (走り駒の方向は PieceSet#get_slides で求める)
+    pub fn get_moves(&self, grid: &Grid) -> Vec<Move> {
+        let moves = self.get_movement(grid).get_moves();
+        if grid.player == 0 {
+            moves
+        } else {
+            moves.iter().map(|&m| Move {x: -m.x, y: -m.y}).collect()
+        }
+    }
+
+    // 盤の端か他の駒まで走る方向
+    pub fn get_slides(&self, grid: &Grid) -> Vec<Move> {
+        let slides = self.get_movement(grid).slides;
+        // 後手の駒は方向を反転させる (ビットの並びを逆にする)
+        let slides = if grid.player == 0 {slides} else {slides.reverse_bits()};
+        (0..8).filter(|&direction| slides & (1 << direction) != 0).map(|direction| DIRECTIONS[direction]).collect()
+    }
+
+    // 盤面の表示に使う1文字
+    pub fn get_name(&self, piece: Piece, promoted: bool) -> char {
+        match piece {
+            Piece::Empty => '・',
+            Piece::王将 => '王',
+            piece if promoted => self.get_definition(piece).promotion.expect("The piece cannot promote").0,
+            piece => self.get_definition(piece).name,
+        }
+    }
+
+    pub fn get_usi_char(&self, piece: Piece) -> char {
+        match piece {
+            Piece::Empty => panic!(),
+            Piece::王将 => 'K',
+            piece => self.get_definition(piece).usi,
+        }
+    }
+
+    pub fn from_usi_char(&self, letter: char) -> Option<Piece> {
+        let letter = letter.to_ascii_uppercase();
+
+        if letter == 'K' {
+            return Some(Piece::王将);
+        }
+
+        self.pieces.iter().position(|definition| definition.usi == letter).map(Piece::from_hand_index)
+    }
+
+    // 持ち駒の種類ごとの最大枚数の既定値
+    pub fn get_capacities(&self) -> [u8; 7] {
+        let mut capacities = [0; 7];
+        for (capacity, definition) in capacities.iter_mut().zip(self.pieces.iter()) {
+            *capacity = definition.capacity;
+        }
+        capacities
+    }
+
+    // 持ち駒の種類ごとの、成っていない駒が存在できない段の数の既定値
+    pub fn get_forced_promotion_rows(&self) -> [u8; 7] {
+        let mut rows = [0; 7];
+        for (row, definition) in rows.iter_mut().zip(self.pieces.iter()) {
+            *row = definition.forced_promotion_rows;
+        }
+        rows
+    }
+
+    fn build_tables(&self) -> PieceTables {
+        let mut tables = PieceTables {
+            slides: [0; 29],
+            step_attacks: [[[[0; MAX_SQUARES]; 29]; MAX_BOARD_SIZE]; MAX_BOARD_SIZE],
+        };
+
+        for code in 1..29 {
+            let grid = Grid::from_i(code as u8);
+
+            // 成れない駒の成った駒を表す値は使われない
+            if grid.promoted && !self.is_promotable(grid.piece) {
+                continue;
+            }
+
+            let moves = self.get_moves(&grid);
+            let slides = self.get_movement(&grid).slides;
+            // 後手の駒は方向を反転させる (ビットの並びを逆にする)
+            tables.slides[code] = if grid.player == 0 {slides} else {slides.reverse_bits()};
+
+            for width in 1..=MAX_BOARD_SIZE {
+                for height in 1..=MAX_BOARD_SIZE {
+                    let table = &mut tables.step_attacks[width - 1][height - 1][code];
+
+                    for (square, attacks) in table.iter_mut().enumerate().take(width * height) {
+                        let x = (square % width) as i8;
+                        let y = (square / width) as i8;
+
+                        for piece_move in moves.iter() {
+                            let target_x = x + piece_move.x;
+                            let target_y = y + piece_move.y;
+
+                            if target_x >= 0 && target_x < width as i8 && target_y >= 0 && target_y < height as i8 {
+                                *attacks |= 1 << (target_y as usize * width + target_x as usize);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        tables
+    }
+
+    // Rules#pieces に格納するインデックス
+    pub fn register(&self) -> u8 {
+        PIECE_SETS.register(self, &STANDARD_PIECE_SET)
+    }
+
+    pub fn get(index: u8) -> &'static PieceSet {
+        PIECE_SETS.get(index, &STANDARD_PIECE_SET)
+    }
+
+    // PieceSet#register のインデックスの駒の移動先の表
+    pub fn get_tables(index: u8) -> &'static PieceTables {
+        PIECE_TABLES[index as usize].get_or_init(|| Box::leak(Box::new(PieceSet::get(index).build_tables())))
+    }
+}
+
+impl Default for PieceSet {
+    fn default() -> PieceSet {
+        STANDARD_PIECE_SET
+    }
+}
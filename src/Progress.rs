@@ -0,0 +1,116 @@
+use std::io::{self, Write};
+
+// 進み具合を報告する処理の種類
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stage {
+    // BoardMap::from_pieces_with_progress による盤面の生成 (全体の数は分からない)
+    Generation,
+    // BoardMap#solve_with_progress による深さごとの探索
+    Solving(u8),
+    // BoardMap#write_with_progress によるデータベースへの書き出し
+    Writing,
+}
+
+// 盤面の生成・探索・書き出しの進み具合を受け取る
+// start で始まり、update が何度か呼ばれ、finish で終わる
+pub trait Progress {
+    // total は処理する盤面の数 (分からない場合は None)
+    fn start(&mut self, stage: Stage, total: Option<usize>);
+    // count はこの処理で今までに処理した盤面の数
+    fn update(&mut self, count: usize);
+    fn finish(&mut self);
+}
+
+// 何も表示しない (テストやライブラリとして使う場合など)
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SilentProgress;
+
+impl Progress for SilentProgress {
+    fn start(&mut self, _stage: Stage, _total: Option<usize>) {}
+    fn update(&mut self, _count: usize) {}
+    fn finish(&mut self) {}
+}
+
+// 進捗バーの幅
+const BAR_WIDTH: usize = 40;
+// 全体の数が分からない場合に表示を更新する間隔
+const COUNT_INTERVAL: usize = 100000;
+
+// 標準エラー出力に進捗バーを表示する
+#[derive(Clone, Debug)]
+pub struct TerminalProgress {
+    label: String,
+    total: Option<usize>,
+    count: usize,
+    // 最後に表示した千分率 (全体の数が分からない場合は盤面の数)
+    shown: Option<usize>,
+}
+
+impl Default for TerminalProgress {
+    fn default() -> TerminalProgress {
+        TerminalProgress::new()
+    }
+}
+
+impl TerminalProgress {
+    pub fn new() -> TerminalProgress {
+        TerminalProgress {
+            label: String::new(),
+            total: None,
+            count: 0,
+            shown: None,
+        }
+    }
+
+    fn draw(&self) {
+        let line = match self.total {
+            Some(total) => {
+                // 全体の数が 0 の場合は終わっているものとして表示する
+                let filled = (self.count * BAR_WIDTH).checked_div(total).unwrap_or(BAR_WIDTH);
+                let percentage = (self.count * 100).checked_div(total).unwrap_or(100);
+
+                format!("{}: [{}{}] {}% ({}/{})", self.label, "#".repeat(filled), "-".repeat(BAR_WIDTH - filled), percentage, self.count, total)
+            },
+            None => format!("{}: {} boards", self.label, self.count),
+        };
+
+        let mut stderr = io::stderr();
+        let _ = write!(stderr, "\r{}", line);
+        let _ = stderr.flush();
+    }
+}
+
+impl Progress for TerminalProgress {
+    fn start(&mut self, stage: Stage, total: Option<usize>) {
+        self.label = match stage {
+            Stage::Generation => "Generating boards".to_string(),
+            Stage::Solving(depth) => format!("Digging Depth-{}", depth),
+            Stage::Writing => "Writing boards".to_string(),
+        };
+        self.total = total;
+        self.count = 0;
+        self.shown = None;
+        self.draw();
+    }
+
+    fn update(&mut self, count: usize) {
+        self.count = count;
+
+        // 表示が変わる場合だけ書き直す
+        let shown = match self.total {
+            Some(total) if total > 0 => count * 1000 / total,
+            Some(_) => 0,
+            None => count / COUNT_INTERVAL,
+        };
+
+        if self.shown != Some(shown) {
+            self.shown = Some(shown);
+            self.draw();
+        }
+    }
+
+    fn finish(&mut self) {
+        self.draw();
+        eprintln!();
+    }
+}
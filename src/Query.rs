@@ -0,0 +1,152 @@
+use std::cmp::Ordering;
+use ::Board::{Action, Board, BoardResult};
+use ::BoardMap::{BoardMap, BoardState};
+use ::Piece::Piece;
+use ::Tablebase::Tablebase;
+
+// 盤面を並べる順番 (いずれも大きいものから並べる)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SortKey {
+    // 勝ち・負けまでの手数
+    Depth,
+    // 最善の手順の数
+    Routes,
+    // 手番側の合法手の数
+    Moves,
+}
+
+impl SortKey {
+    pub fn from_name(name: &str) -> Result<SortKey, String> {
+        match name {
+            "depth" => Ok(SortKey::Depth),
+            "routes" => Ok(SortKey::Routes),
+            "moves" => Ok(SortKey::Moves),
+            _ => Err(format!("Unknown sort key: {} (expected depth, routes or moves)", name)),
+        }
+    }
+}
+
+// 盤面を選ぶ条件。None の条件では絞り込まない
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Query {
+    pub sort_key: SortKey,
+    pub limit: usize,
+    // 手番側から見た勝敗
+    pub result: Option<BoardResult>,
+    // 手番側が盤上と持ち駒に持っている王将以外の駒 (成った駒は成る前の駒として数え、持ち駒の番号順に並べる)
+    pub material: Option<Vec<Piece>>,
+    pub is_good: Option<bool>,
+}
+
+impl Default for Query {
+    fn default() -> Query {
+        Query {
+            sort_key: SortKey::Depth,
+            limit: 10,
+            result: None,
+            material: None,
+            is_good: None,
+        }
+    }
+}
+
+// Query に合う盤面
+#[derive(Clone, Copy, Debug)]
+pub struct QueryResult {
+    pub board: Board,
+    pub state: BoardState,
+    // 手番側の合法手の数
+    pub moves: usize,
+}
+
+// 手番側が持っている王将以外の駒を持ち駒の番号順に並べたもの
+pub fn get_material(board: &Board) -> Vec<Piece> {
+    let mut material: Vec<Piece> = Vec::new();
+    let (width, height) = board.get_size();
+    let player = board.side_to_move();
+
+    for y in 0..height {
+        for x in 0..width {
+            let grid = board.get_grid(x, y);
+
+            if grid.piece != Piece::Empty && grid.piece != Piece::王将 && grid.player == player {
+                material.push(grid.piece);
+            }
+        }
+    }
+
+    for i in 0..7 {
+        let piece = Piece::from_hand_index(i);
+
+        for _ in 0..board.get_hand_count(player, piece) {
+            material.push(piece);
+        }
+    }
+
+    material.sort_by_key(|piece| piece.to_hand_index());
+    material
+}
+
+fn get_sort_value(result: &QueryResult, sort_key: SortKey) -> Option<u32> {
+    match sort_key {
+        SortKey::Depth => result.state.depth.map(|depth| depth as u32),
+        SortKey::Routes => result.state.routes,
+        SortKey::Moves => Some(result.moves as u32),
+    }
+}
+
+// board_map から query に合う盤面を選び、並べ替えて最大 query.limit 個返す
+// 値のない盤面 (勝敗の決まらない盤面の手数など) は最後に並べ、同じ値の盤面は盤面の表現の順に並べる
+pub fn query(board_map: &BoardMap, query: &Query) -> Vec<QueryResult> {
+    // 合法手の数は、合法手の数で並べる場合のほかは残った盤面についてだけ数える
+    let by_moves = query.sort_key == SortKey::Moves;
+    let mut results: Vec<QueryResult> = board_map.map.iter().filter(|&(board, state)| {
+        query.result.is_none_or(|result| state.result == result) &&
+        query.is_good.is_none_or(|is_good| state.is_good == Some(is_good)) &&
+        query.material.as_ref().is_none_or(|material| &get_material(board) == material)
+    }).map(|(&board, &state)| QueryResult {
+        board,
+        state,
+        moves: if by_moves {board.legal_moves().len()} else {0},
+    }).collect();
+
+    results.sort_by(|a, b| {
+        match (get_sort_value(a, query.sort_key), get_sort_value(b, query.sort_key)) {
+            (Some(a_value), Some(b_value)) => b_value.cmp(&a_value),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }.then_with(|| (a.board.grids, a.board.hands).cmp(&(b.board.grids, b.board.hands)))
+    });
+
+    results.truncate(query.limit);
+
+    if !by_moves {
+        for result in results.iter_mut() {
+            result.moves = result.board.legal_moves().len();
+        }
+    }
+
+    results
+}
+
+// データベースの最善手をたどった読み筋 (勝敗が決まるまで、最大 max_length 手)
+// 勝敗のつかない盤面では勝敗のつかない手をたどるので、max_length で打ち切る
+pub fn get_principal_variation(tablebase: &mut Tablebase, board: &Board, max_length: usize) -> Vec<Action> {
+    let mut variation: Vec<Action> = Vec::new();
+    let mut board = *board;
+
+    while variation.len() < max_length {
+        let action = match tablebase.get_best_action(&board) {
+            // 深さ0の勝ちの盤面では王将を取れ、深さ1の負けの盤面ではどの手を指しても王将を取られる
+            Some((state, _)) if state.depth.is_some_and(|depth| depth <= 1) => break,
+            Some((_, Some(action))) => action,
+            _ => break,
+        };
+
+        board = board.apply(&action);
+        variation.push(action);
+    }
+
+    variation
+}
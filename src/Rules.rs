@@ -0,0 +1,250 @@
+use ::Board::BoardResult;
+use ::Grid::MAX_BOARD_SIZE;
+use ::Piece::Piece;
+use ::PieceSet::PieceSet;
+use ::util::Registry;
+
+// ステルスメイト (指せる手がない盤面) の扱い
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Stalemate {
+    // 指せない側の負け
+    Lose,
+    // 指せない側の勝ち
+    Win,
+    // 引き分け
+    Draw,
+}
+
+// 盤の大きさや成り、持ち駒などに関するルール
+// 段の数はいずれも手番側から見た敵陣の端 (先手なら y = 0) から数える
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Rules {
+    // 盤の幅 (筋の数) と高さ (段の数)
+    pub width: u8,
+    pub height: u8,
+    // 駒の定義 (PieceSet#register のインデックス)
+    pub pieces: u8,
+    // 成ることのできる段の数 (移動元か移動先がこの段にあれば成れる)
+    pub promotion_rows: u8,
+    // 持ち駒の種類ごとの、成っていない駒が存在できない段の数 (行き所のない駒)
+    // この段に移動する場合は必ず成り、この段には打てない
+    pub forced_promotion_rows: [u8; 7],
+    // 成れる場合に成らない手 (不成) を指せるか
+    pub allow_non_promotion: bool,
+    // 取った駒を打てるか (打てない場合、取った駒は盤上から取り除かれる)
+    pub allow_drops: bool,
+    // 指せる手がない場合の手番側の勝敗
+    pub stalemate: Stalemate,
+}
+
+// 本将棋のルールを3x3の盤に当てはめたもの
+pub const DEFAULT_RULES: Rules = Rules {
+    width: 3,
+    height: 3,
+    pieces: 0,
+    promotion_rows: 1,
+    // 桂馬は2段、香車と歩兵は1段
+    forced_promotion_rows: [0, 0, 0, 0, 2, 1, 1],
+    allow_non_promotion: true,
+    allow_drops: true,
+    stalemate: Stalemate::Lose,
+};
+
+// Rules#register で登録した既定以外の Rules
+static RULES: Registry<Rules> = Registry::new();
+
+impl Rules {
+    // 盤面やデータベースの名前に使う表記からルールを読み込む
+    // "-" で区切った以下の項目からなり、省略した項目は既定のルールになる
+    // * b<幅>x<高さ>: 盤の大きさ
+    // * p<名前>: PieceSet::load で読み込む駒の定義 (成っていない駒が存在できない段の数の既定値も変わる)
+    // * z<段の数>: 成ることのできる段の数
+    // * f<7桁の数字>: 持ち駒の番号順の、成っていない駒が存在できない段の数
+    // * m: 不成を指せない
+    // * d: 持ち駒を打てない
+    // * s<l|w|d>: ステルスメイトを指せない側の負け、勝ち、引き分けとする
+    pub fn from_name(name: &str) -> Result<Rules, String> {
+        let mut rules = DEFAULT_RULES;
+        let mut piece_set: Option<PieceSet> = None;
+        let mut forced_promotion_rows: Option<[u8; 7]> = None;
+
+        for item in name.split('-') {
+            // 先頭の1文字が種類で、残りが値 (先頭が ASCII 以外の文字の場合も分けられるように文字単位で分ける)
+            let mut letters = item.chars();
+            let key = match letters.next() {
+                Some(key) => key,
+                None => continue,
+            };
+            let value = letters.as_str();
+
+            match key {
+                'b' => {
+                    let size: Vec<u8> = value.split('x').filter_map(|length| length.parse().ok()).collect();
+
+                    if size.len() != 2 || value.split('x').count() != 2 {
+                        return Err(format!("Invalid board size: {}", item));
+                    }
+
+                    rules.width = size[0];
+                    rules.height = size[1];
+                },
+                'p' => {
+                    piece_set = Some(PieceSet::load(value)?);
+                },
+                'z' => {
+                    rules.promotion_rows = value.parse().map_err(|_| format!("Invalid promotion rows: {}", item))?;
+                },
+                'f' => {
+                    let digits: Vec<u8> = value.chars().filter_map(|letter| letter.to_digit(10)).map(|digit| digit as u8).collect();
+
+                    if digits.len() != 7 || value.len() != 7 {
+                        return Err(format!("Invalid forced promotion rows: {}", item));
+                    }
+
+                    let mut rows = [0; 7];
+                    rows.copy_from_slice(&digits);
+                    forced_promotion_rows = Some(rows);
+                },
+                'm' if value.is_empty() => {
+                    rules.allow_non_promotion = false;
+                },
+                'd' if value.is_empty() => {
+                    rules.allow_drops = false;
+                },
+                's' => {
+                    rules.stalemate = match value {
+                        "l" => Stalemate::Lose,
+                        "w" => Stalemate::Win,
+                        "d" => Stalemate::Draw,
+                        _ => return Err(format!("Invalid stalemate rule: {}", item)),
+                    };
+                },
+                _ => return Err(format!("Unknown rule: {}", item)),
+            }
+        }
+
+        if let Some(piece_set) = piece_set {
+            rules = rules.with_piece_set(&piece_set);
+        }
+
+        if let Some(rows) = forced_promotion_rows {
+            rules.forced_promotion_rows = rows;
+        }
+
+        rules.validate()?;
+        Ok(rules)
+    }
+
+    // Rules::from_name で読み込める表記 (既定のルールは空文字列)
+    pub fn get_name(&self) -> String {
+        let mut items: Vec<String> = Vec::new();
+
+        if self.width != DEFAULT_RULES.width || self.height != DEFAULT_RULES.height {
+            items.push(format!("b{}x{}", self.width, self.height));
+        }
+
+        let piece_set = self.get_piece_set();
+
+        if self.pieces != DEFAULT_RULES.pieces {
+            items.push(format!("p{}", piece_set.name));
+        }
+
+        if self.promotion_rows != DEFAULT_RULES.promotion_rows {
+            items.push(format!("z{}", self.promotion_rows));
+        }
+
+        if self.forced_promotion_rows != piece_set.get_forced_promotion_rows() {
+            items.push(format!("f{}", self.forced_promotion_rows.iter().map(|rows| rows.to_string()).collect::<Vec<_>>().concat()));
+        }
+
+        if !self.allow_non_promotion {
+            items.push("m".to_string());
+        }
+
+        if !self.allow_drops {
+            items.push("d".to_string());
+        }
+
+        match self.stalemate {
+            Stalemate::Lose => {},
+            Stalemate::Win => items.push("sw".to_string()),
+            Stalemate::Draw => items.push("sd".to_string()),
+        }
+
+        items.join("-")
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let max_size = MAX_BOARD_SIZE as u8;
+
+        if self.width == 0 || self.width > max_size || self.height == 0 || self.height > max_size || self.get_squares() < 2 {
+            return Err(format!("Board size must be between 1 and {} and have at least 2 squares: {}x{}", max_size, self.width, self.height));
+        }
+
+        if self.promotion_rows > self.height {
+            return Err(format!("Promotion rows must be at most {}: {}", self.height, self.promotion_rows));
+        }
+
+        let piece_set = self.get_piece_set();
+
+        for (i, &rows) in self.forced_promotion_rows.iter().enumerate() {
+            let piece = Piece::from_hand_index(i);
+
+            if rows > self.height || (rows > 0 && !piece_set.is_promotable(piece)) {
+                return Err(format!("Invalid forced promotion rows of {}: {}", piece_set.get_name(piece, false), rows));
+            }
+        }
+
+        Ok(())
+    }
+
+    // 駒の定義を piece_set に置き換える (成っていない駒が存在できない段の数は piece_set の既定値になる)
+    pub fn with_piece_set(&self, piece_set: &PieceSet) -> Rules {
+        Rules {
+            pieces: piece_set.register(),
+            forced_promotion_rows: piece_set.get_forced_promotion_rows(),
+            ..*self
+        }
+    }
+
+    pub fn get_piece_set(&self) -> &'static PieceSet {
+        PieceSet::get(self.pieces)
+    }
+
+    // 盤のマスの数
+    pub fn get_squares(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    // 成っていない piece が存在できない段の数
+    pub fn get_forced_promotion_rows(&self, piece: Piece) -> u8 {
+        match piece {
+            Piece::Empty | Piece::王将 => 0,
+            _ => self.forced_promotion_rows[piece.to_hand_index() as usize],
+        }
+    }
+
+    // ステルスメイトの盤面の、手番側から見た状態 (引き分けは Unknown)
+    pub fn get_stalemate_result(&self) -> BoardResult {
+        match self.stalemate {
+            Stalemate::Lose => BoardResult::Lose,
+            Stalemate::Win => BoardResult::Win,
+            Stalemate::Draw => BoardResult::Unknown,
+        }
+    }
+
+    // Board#rules に格納するインデックス
+    pub fn register(&self) -> u8 {
+        RULES.register(self, &DEFAULT_RULES)
+    }
+
+    pub fn get(index: u8) -> &'static Rules {
+        RULES.get(index, &DEFAULT_RULES)
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        DEFAULT_RULES
+    }
+}
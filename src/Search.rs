@@ -0,0 +1,279 @@
+extern crate fnv;
+
+use self::fnv::FnvHashMap;
+use ::Board::{Action, Board, BoardResult};
+use ::BoardMap::BoardState;
+use ::Tablebase::Tablebase;
+
+// 王を取られる盤面の評価値。勝ちの盤面は MATE から王を取るまでの手数を引いた値で表す
+const MATE: i16 = 1000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+// 置換表のエントリ
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    score: i16,
+    bound: Bound,
+    // 探索した残り手数 (データベースや終局で確定した値は u8::MAX)
+    limit: u8,
+    // 最善手の合法手中のインデックス
+    best_index: Option<usize>,
+}
+
+// 置換表つきのαβ探索。データベースが与えられた場合は末端でデータベースを引く
+pub struct Search<'a> {
+    tablebase: Option<&'a mut Tablebase>,
+    table: FnvHashMap<Board, Entry>,
+    pub nodes: u64,
+}
+
+fn to_score(state: &BoardState) -> i16 {
+    match state.result {
+        BoardResult::Win => MATE - state.depth.unwrap() as i16,
+        BoardResult::Lose => -(MATE - state.depth.unwrap() as i16),
+        BoardResult::Unknown => 0,
+    }
+}
+
+fn to_state(score: i16) -> BoardState {
+    BoardState {
+        result: if score > 0 {
+            BoardResult::Win
+        } else if score < 0 {
+            BoardResult::Lose
+        } else {
+            BoardResult::Unknown
+        },
+        depth: if score == 0 {None} else {Some((MATE - score.abs()) as u8)},
+        routes: None,
+        is_good: None,
+    }
+}
+
+// 遷移先の評価値を遷移元から見た評価値に変換する (王を取るまでの手数が1つ増える)
+fn negate(score: i16) -> i16 {
+    if score > 0 {
+        -score + 1
+    } else if score < 0 {
+        -score - 1
+    } else {
+        0
+    }
+}
+
+// negate の逆変換。遷移元の探索窓を遷移先の探索窓に変換するのに使う
+fn unnegate(score: i16) -> i16 {
+    if score > 0 {
+        -(score + 1)
+    } else if score < 0 {
+        -(score - 1)
+    } else {
+        0
+    }
+}
+
+// 指せる手がない盤面の評価値。ステルスメイトの勝敗はルールによる
+fn get_terminal_score(board: &Board) -> i16 {
+    if !board.is_stalemate() {
+        return -(MATE - 1);
+    }
+
+    match board.get_rules().get_stalemate_result() {
+        BoardResult::Win => MATE,
+        BoardResult::Lose => -(MATE - 1),
+        BoardResult::Unknown => 0,
+    }
+}
+
+impl<'a> Default for Search<'a> {
+    fn default() -> Search<'a> {
+        Search::new()
+    }
+}
+
+impl<'a> Search<'a> {
+    pub fn new() -> Search<'a> {
+        Search {
+            tablebase: None,
+            table: FnvHashMap::default(),
+            nodes: 0,
+        }
+    }
+
+    pub fn with_tablebase(tablebase: &'a mut Tablebase) -> Search<'a> {
+        Search {
+            tablebase: Some(tablebase),
+            table: FnvHashMap::default(),
+            nodes: 0,
+        }
+    }
+
+    fn store(&mut self, board: &Board, score: i16, bound: Bound, limit: u8, best_index: Option<usize>) {
+        self.table.insert(*board, Entry {
+            score,
+            bound,
+            limit,
+            best_index,
+        });
+    }
+
+    // board は手番側が先手になるように反転した盤面
+    fn alpha_beta(&mut self, board: &Board, limit: u8, alpha: i16, beta: i16) -> i16 {
+        self.nodes += 1;
+
+        let mut hint = None;
+
+        if let Some(entry) = self.table.get(board) {
+            if entry.limit >= limit {
+                match entry.bound {
+                    Bound::Exact => return entry.score,
+                    Bound::Lower if entry.score >= beta => return entry.score,
+                    Bound::Upper if entry.score <= alpha => return entry.score,
+                    _ => {},
+                }
+            }
+
+            hint = entry.best_index;
+        }
+
+        let probed = match self.tablebase {
+            Some(ref mut tablebase) => tablebase.probe(board),
+            None => None,
+        };
+
+        if let Some(state) = probed {
+            let score = to_score(&state);
+            self.store(board, score, Bound::Exact, u8::MAX, None);
+            return score;
+        }
+
+        let actions = board.get_legal_actions();
+
+        // 詰みまたはステルスメイト
+        if actions.is_empty() {
+            let score = get_terminal_score(board);
+            self.store(board, score, Bound::Exact, u8::MAX, None);
+            return score;
+        }
+
+        if limit == 0 {
+            return 0;
+        }
+
+        // 前回の探索での最善手から調べる
+        let mut order: Vec<usize> = (0..actions.len()).collect();
+        if let Some(index) = hint {
+            if index < order.len() {
+                order.remove(index);
+                order.insert(0, index);
+            }
+        }
+
+        let original_alpha = alpha;
+        let mut alpha = alpha;
+        let mut best_score = -MATE;
+        let mut best_index = None;
+
+        for index in order {
+            let transition = board.apply_action(&actions[index]);
+            let score = negate(self.alpha_beta(&transition, limit - 1, unnegate(beta), unnegate(alpha)));
+
+            if best_index.is_none() || score > best_score {
+                best_score = score;
+                best_index = Some(index);
+            }
+
+            if best_score > alpha {
+                alpha = best_score;
+            }
+
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score <= original_alpha {
+            Bound::Upper
+        } else {
+            Bound::Exact
+        };
+
+        self.store(board, best_score, bound, limit, best_index);
+
+        best_score
+    }
+
+    // 最大 max_depth 手まで反復深化で探索し、手番側から見た盤面の状態と最善手 (Board#legal_moves と同じ座標) を返す
+    // 勝ちは見つかった時点で打ち切るので最短の手数になる。勝敗が証明できなかった場合は Unknown を返す
+    pub fn search(&mut self, board: &Board, max_depth: u8) -> (BoardState, Option<Action>) {
+        let normalized = board.normalize();
+        let mut actions = normalized.get_legal_actions();
+
+        if actions.is_empty() {
+            return (to_state(get_terminal_score(&normalized)), None);
+        }
+
+        let mut best_score = 0;
+        let mut best_index = 0;
+
+        for limit in 0..max_depth {
+            let mut alpha = -MATE - 1;
+            best_score = -MATE - 1;
+
+            for (index, action) in actions.iter().enumerate() {
+                let transition = normalized.apply_action(action);
+                let score = negate(self.alpha_beta(&transition, limit, -MATE - 1, unnegate(alpha)));
+
+                if score > best_score {
+                    best_score = score;
+                    best_index = index;
+                }
+
+                if best_score > alpha {
+                    alpha = best_score;
+                }
+            }
+
+            self.store(&normalized, best_score, Bound::Exact, limit + 1, Some(best_index));
+
+            if best_score != 0 {
+                break;
+            }
+        }
+
+        (to_state(best_score), Some(board.normalize_action(actions.swap_remove(best_index))))
+    }
+
+    // 置換表に記録された最善手をたどって読み筋を返す (置換表の盤面は手番の向きによらず引ける)
+    pub fn get_principal_variation(&self, board: &Board, max_length: usize) -> Vec<Action> {
+        let mut variation: Vec<Action> = Vec::new();
+        let mut board = *board;
+
+        while variation.len() < max_length {
+            let index = match self.table.get(&board) {
+                Some(&Entry {best_index: Some(index), ..}) => index,
+                _ => break,
+            };
+
+            let mut actions = board.legal_moves();
+
+            if index >= actions.len() {
+                break;
+            }
+
+            let action = actions.swap_remove(index);
+            board = board.apply(&action);
+            variation.push(action);
+        }
+
+        variation
+    }
+}
@@ -0,0 +1,157 @@
+use std::time::Duration;
+use ::Board::BoardResult;
+use ::BoardMap::BoardMap;
+
+// 解いた駒構成の盤面の数などの統計 (main.rs がデータベースと同じ場所に JSON と CSV で書き出す)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Statistics {
+    pub config: String,
+    pub rules: String,
+    pub boards: u32,
+    // 深さごとの勝ち・負けの盤面の数 (インデックスが深さ)
+    pub wins: Vec<u32>,
+    pub loses: Vec<u32>,
+    pub unknowns: u32,
+    pub 打ち歩詰め_count: u32,
+    pub is_good_count: u32,
+    pub max_depth: Option<u8>,
+    // 盤面の生成、データベースからの読み込み、探索、書き出しにかかった時間
+    pub generation_time: Duration,
+    pub seed_time: Duration,
+    pub solve_time: Duration,
+    pub write_time: Duration,
+}
+
+// JSON の文字列として書き出す
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+
+    escaped.push('"');
+
+    for letter in text.chars() {
+        match letter {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            letter if (letter as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", letter as u32)),
+            letter => escaped.push(letter),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+fn format_counts(counts: &[u32]) -> String {
+    counts.iter().map(|count| count.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+impl Statistics {
+    // 解き終えた board_map の盤面を数える。時間は 0 のままなので呼び出し側で設定する
+    pub fn from_board_map(config: &str, rules: &str, board_map: &BoardMap, 打ち歩詰め_count: u32) -> Statistics {
+        let mut statistics = Statistics {
+            config: config.to_string(),
+            rules: rules.to_string(),
+            boards: board_map.map.len() as u32,
+            wins: Vec::new(),
+            loses: Vec::new(),
+            unknowns: 0,
+            打ち歩詰め_count,
+            is_good_count: 0,
+            max_depth: None,
+            generation_time: Duration::default(),
+            seed_time: Duration::default(),
+            solve_time: Duration::default(),
+            write_time: Duration::default(),
+        };
+
+        for state in board_map.map.values() {
+            if state.is_good == Some(true) {
+                statistics.is_good_count += 1;
+            }
+
+            let counts = match state.result {
+                BoardResult::Win => &mut statistics.wins,
+                BoardResult::Lose => &mut statistics.loses,
+                BoardResult::Unknown => {
+                    statistics.unknowns += 1;
+                    continue;
+                },
+            };
+
+            let depth = state.depth.expect("Depth of the board was not set");
+
+            if counts.len() <= depth as usize {
+                counts.resize(depth as usize + 1, 0);
+            }
+
+            counts[depth as usize] += 1;
+            statistics.max_depth = statistics.max_depth.max(Some(depth));
+        }
+
+        // 勝ちと負けの深さの一覧を同じ長さにそろえる
+        let length = statistics.max_depth.map_or(0, |depth| depth as usize + 1);
+        statistics.wins.resize(length, 0);
+        statistics.loses.resize(length, 0);
+
+        statistics
+    }
+
+    pub fn get_win_count(&self) -> u32 {
+        self.wins.iter().sum()
+    }
+
+    pub fn get_lose_count(&self) -> u32 {
+        self.loses.iter().sum()
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut lines: Vec<String> = Vec::new();
+
+        lines.push(format!("  \"config\": {},", escape_json(&self.config)));
+        lines.push(format!("  \"rules\": {},", escape_json(&self.rules)));
+        lines.push(format!("  \"boards\": {},", self.boards));
+        lines.push(format!("  \"wins\": {},", self.get_win_count()));
+        lines.push(format!("  \"loses\": {},", self.get_lose_count()));
+        lines.push(format!("  \"unknowns\": {},", self.unknowns));
+        lines.push(format!("  \"uchifuzume\": {},", self.打ち歩詰め_count));
+        lines.push(format!("  \"is_good\": {},", self.is_good_count));
+        lines.push(format!("  \"max_depth\": {},", self.max_depth.map_or("null".to_string(), |depth| depth.to_string())));
+        lines.push(format!("  \"wins_by_depth\": [{}],", format_counts(&self.wins)));
+        lines.push(format!("  \"loses_by_depth\": [{}],", format_counts(&self.loses)));
+        lines.push("  \"seconds\": {".to_string());
+        lines.push(format!("    \"generation\": {:.3},", self.generation_time.as_secs_f64()));
+        lines.push(format!("    \"seed\": {:.3},", self.seed_time.as_secs_f64()));
+        lines.push(format!("    \"solve\": {:.3},", self.solve_time.as_secs_f64()));
+        lines.push(format!("    \"write\": {:.3}", self.write_time.as_secs_f64()));
+        lines.push("  }".to_string());
+
+        format!("{{\n{}\n}}\n", lines.join("\n"))
+    }
+
+    // 1行に1つの値を書く縦長の形式 (metric,depth,value)。深さごとでない値の depth は空
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("metric,depth,value\n");
+        let mut push = |metric: &str, depth: Option<usize>, value: String| {
+            csv.push_str(&format!("{},{},{}\n", metric, depth.map_or(String::new(), |depth| depth.to_string()), value));
+        };
+
+        push("boards", None, self.boards.to_string());
+        push("wins", None, self.get_win_count().to_string());
+        push("loses", None, self.get_lose_count().to_string());
+        push("unknowns", None, self.unknowns.to_string());
+        push("uchifuzume", None, self.打ち歩詰め_count.to_string());
+        push("is_good", None, self.is_good_count.to_string());
+        push("max_depth", None, self.max_depth.map_or(String::new(), |depth| depth.to_string()));
+        push("generation_seconds", None, format!("{:.3}", self.generation_time.as_secs_f64()));
+        push("seed_seconds", None, format!("{:.3}", self.seed_time.as_secs_f64()));
+        push("solve_seconds", None, format!("{:.3}", self.solve_time.as_secs_f64()));
+        push("write_seconds", None, format!("{:.3}", self.write_time.as_secs_f64()));
+
+        for (depth, (wins, loses)) in self.wins.iter().zip(self.loses.iter()).enumerate() {
+            push("wins", Some(depth), wins.to_string());
+            push("loses", Some(depth), loses.to_string());
+        }
+
+        csv
+    }
+}
@@ -0,0 +1,512 @@
+extern crate fnv;
+extern crate rusqlite;
+
+use self::fnv::{FnvHashMap, FnvHashSet};
+use self::rusqlite::{Connection, OpenFlags, OptionalExtension, Row};
+use self::rusqlite::params;
+use self::rusqlite::types::Value;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use ::Board::{Action, Board, BoardResult};
+use ::BoardMap::BoardState;
+use ::Material::Material;
+use ::Piece::Piece;
+use ::Rules::Rules;
+use ::util::HandLayout;
+
+// データベースに書き出された盤面の数と最大の深さ (深さ0の盤面は書き出されていないので含まない)
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct DatabaseSummary {
+    pub wins: u32,
+    pub loses: u32,
+    pub unknowns: u32,
+    pub max_win_depth: Option<u8>,
+    pub max_lose_depth: Option<u8>,
+}
+
+// Tablebase::merge でまとめたデータベースの一覧
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct MergeSummary {
+    // まとめた駒構成 (Tablebase::get_database_name の形式) と盤面の数
+    pub materials: Vec<(String, u32)>,
+    // まとめなかったファイルとその理由
+    pub skipped: Vec<(String, String)>,
+}
+
+// 駒構成ごとに生成された boards/*.sqlite3 から盤面の状態を引く
+pub struct Tablebase {
+    directory: String,
+    // 駒構成ごとのデータベース (ファイルが存在しない場合は None)
+    connections: FnvHashMap<String, Option<Connection>>,
+    // Tablebase::merge でまとめたデータベースと、含まれる駒構成 (directory がこのファイルの場合)
+    combined: Option<(Connection, FnvHashSet<String>)>,
+    // directory にあるデータベースの駒構成とルールとファイル名 (拡張子を除く)。最初に使うときに読み込む
+    databases: Option<Vec<(Material, Rules, String)>>,
+}
+
+// 盤面の状態を表す列 (result, depth, routes, is_good) を読む
+fn get_state(row: &Row) -> rusqlite::Result<BoardState> {
+    Ok(BoardState {
+        result: match row.get::<_, Option<u8>>(0)? {
+            Some(0) => BoardResult::Lose,
+            Some(1) => BoardResult::Win,
+            None => BoardResult::Unknown,
+            _ => panic!(),
+        },
+        depth: row.get(1)?,
+        routes: row.get(2)?,
+        is_good: row.get(3)?,
+    })
+}
+
+impl Tablebase {
+    // directory は駒構成ごとのデータベースのあるディレクトリか、Tablebase::merge でまとめたデータベースのファイル
+    pub fn new(directory: String) -> Tablebase {
+        let combined = if Path::new(&directory).is_file() {
+            Tablebase::open_combined(&directory).ok()
+        } else {
+            None
+        };
+
+        Tablebase {
+            directory,
+            connections: FnvHashMap::default(),
+            combined,
+            databases: None,
+        }
+    }
+
+    fn open_combined(path: &str) -> Result<(Connection, FnvHashSet<String>), String> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(|error| error.to_string())?;
+        let materials = {
+            let mut statement = conn.prepare("SELECT material FROM tables").map_err(|error| error.to_string())?;
+            let rows = statement.query_map([], |row| row.get::<_, String>(0)).map_err(|error| error.to_string())?;
+            rows.collect::<Result<FnvHashSet<String>, _>>().map_err(|error| error.to_string())?
+        };
+
+        Ok((conn, materials))
+    }
+
+    // 王将以外の駒が max_pieces 枚以下の駒構成 (持ち駒の番号順に並べたもの) をすべて列挙する
+    // 駒の種類ごとの枚数は capacities (PieceSet#get_capacities など) 以下にする
+    pub fn enumerate_configs(max_pieces: usize, capacities: [u8; 7]) -> Vec<Vec<Piece>> {
+        let mut configs: Vec<Vec<Piece>> = vec![vec![]];
+        let mut current: Vec<Vec<Piece>> = vec![vec![]];
+
+        for _ in 0..max_pieces {
+            let mut next: Vec<Vec<Piece>> = Vec::new();
+
+            for config in current.iter() {
+                // 駒を番号順に足していけば同じ駒構成は一度しか現れない
+                let start = config.last().map_or(0, |piece| piece.to_hand_index() as usize);
+
+                for (i, &capacity) in capacities.iter().enumerate().skip(start) {
+                    let piece = Piece::from_hand_index(i);
+
+                    if config.iter().filter(|&&other| other == piece).count() < capacity as usize {
+                        let mut config = config.clone();
+                        config.push(piece);
+                        next.push(config);
+                    }
+                }
+            }
+
+            configs.extend(next.iter().cloned());
+            current = next;
+        }
+
+        configs
+    }
+
+    // データベースのファイル名 (拡張子を除く)。既定以外のルールではルールの表記を後ろにつける
+    pub fn get_database_name(config_name: &str, rules: &Rules) -> String {
+        let rules_name = rules.get_name();
+
+        if rules_name.is_empty() {
+            config_name.to_string()
+        } else {
+            format!("{}-{}", config_name, rules_name)
+        }
+    }
+
+    // Tablebase::get_database_name の逆。データベースのファイル名 (拡張子を除く) から駒構成とルールを求める
+    pub fn parse_database_name(database_name: &str) -> Result<(Material, Rules), String> {
+        let (config_name, rules_name) = match database_name.find('-') {
+            Some(index) => (&database_name[..index], &database_name[index + 1..]),
+            None => (database_name, ""),
+        };
+
+        Ok((Material::from_name(config_name)?, Rules::from_name(rules_name)?))
+    }
+
+    // path のデータベースの meta テーブル (BoardMap#write が書き出す駒構成やルールなど) を読み込む
+    pub fn read_meta(path: &str) -> Result<FnvHashMap<String, String>, String> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(|error| error.to_string())?;
+        let mut statement = conn.prepare("SELECT key, value FROM meta").map_err(|error| error.to_string())?;
+        let rows = statement.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        }).map_err(|error| error.to_string())?;
+
+        rows.collect::<Result<FnvHashMap<String, String>, _>>().map_err(|error| error.to_string())
+    }
+
+    // path のデータベースの盤面を勝敗ごとに数える
+    pub fn summarize(path: &str) -> Result<DatabaseSummary, String> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(|error| error.to_string())?;
+        let mut statement = conn.prepare("
+            SELECT result, COUNT(*), MAX(depth) FROM boards GROUP BY result
+        ").map_err(|error| error.to_string())?;
+        let rows = statement.query_map([], |row| {
+            Ok((
+                row.get::<_, Option<u8>>(0)?,
+                row.get::<_, u32>(1)?,
+                row.get::<_, Option<u8>>(2)?,
+            ))
+        }).map_err(|error| error.to_string())?;
+        let mut summary = DatabaseSummary::default();
+
+        for row in rows {
+            match row.map_err(|error| error.to_string())? {
+                (Some(0), count, depth) => {
+                    summary.loses = count;
+                    summary.max_lose_depth = depth;
+                },
+                (Some(1), count, depth) => {
+                    summary.wins = count;
+                    summary.max_win_depth = depth;
+                },
+                (None, count, _) => summary.unknowns = count,
+                (Some(result), _, _) => return Err(format!("Invalid result: {}", result)),
+            }
+        }
+
+        Ok(summary)
+    }
+
+    // directory の駒構成ごとのデータベース (*.sqlite3) を output の1つのデータベースにまとめる
+    // 盤面は駒構成 (Tablebase::get_database_name の形式、material 列) と盤面の組で引けるようにする
+    // 駒構成とルールは meta テーブル (ない場合はファイル名) から求め、求められないファイルや同じ駒構成のファイルはまとめない
+    // 同じディレクトリの一時ファイルにまとめ、すべてのデータベースを書き写せた場合だけ output を置き換える
+    // output がまとめたデータベース (tables テーブルのあるもの) 以外のファイルの場合は上書きしない
+    pub fn merge(directory: &str, output: &str) -> Result<MergeSummary, String> {
+        if Path::new(output).exists() && Tablebase::open_combined(output).is_err() {
+            return Err(format!("{} already exists and is not a combined database", output));
+        }
+
+        let temporary_output = format!("{}.tmp", output);
+        let _ = fs::remove_file(&temporary_output);
+
+        let result = Tablebase::merge_into(directory, output, &temporary_output);
+
+        match result {
+            Ok(_) => fs::rename(&temporary_output, output).map_err(|error| error.to_string())?,
+            Err(_) => {
+                let _ = fs::remove_file(&temporary_output);
+            },
+        }
+
+        result
+    }
+
+    // Tablebase::merge の本体。temporary_output にまとめる (output 自身はまとめない)
+    fn merge_into(directory: &str, output: &str, temporary_output: &str) -> Result<MergeSummary, String> {
+        let conn = Connection::open(temporary_output).map_err(|error| error.to_string())?;
+        let output_path = fs::canonicalize(output).ok();
+
+        conn.execute_batch("
+            PRAGMA journal_mode = OFF;
+            PRAGMA synchronous = OFF;
+
+            CREATE TABLE tables (
+                material TEXT PRIMARY KEY NOT NULL,
+                config TEXT NOT NULL,
+                rules TEXT NOT NULL,
+                boards INTEGER NOT NULL
+            );
+
+            CREATE TABLE boards (
+                material TEXT NOT NULL,
+                board BLOB NOT NULL,
+                result INTEGER,
+                depth INTEGER,
+                routes INTEGER,
+                is_good INTEGER,
+                sfen TEXT,
+                PRIMARY KEY (material, board)
+            ) WITHOUT ROWID;
+
+            CREATE TABLE meta (
+                key TEXT PRIMARY KEY NOT NULL,
+                value TEXT NOT NULL
+            );
+        ").map_err(|error| error.to_string())?;
+
+        let mut paths: Vec<_> = fs::read_dir(directory).map_err(|error| error.to_string())?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.to_string_lossy().ends_with(".sqlite3"))
+            .filter(|path| output_path.is_none() || fs::canonicalize(path).ok() != output_path)
+            .collect();
+        paths.sort();
+
+        let mut summary = MergeSummary::default();
+
+        for path in paths {
+            let path = path.to_string_lossy().to_string();
+            let (config, rules) = match Tablebase::read_config(&path) {
+                Ok(config) => config,
+                Err(message) => {
+                    summary.skipped.push((path, message));
+                    continue;
+                },
+            };
+            let material = config.get_database_name(&rules);
+
+            if summary.materials.iter().any(|(merged, _)| merged == &material) {
+                summary.skipped.push((path, format!("{} is already merged", material)));
+                continue;
+            }
+
+            let count = Tablebase::merge_database(&conn, &path, &config, &rules)
+                .map_err(|message| format!("Failed to merge {}: {}", path, message))?;
+            summary.materials.push((material, count));
+        }
+
+        Tablebase::write_tables(&conn).map_err(|error| error.to_string())?;
+
+        let created_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+
+        conn.execute_batch("
+            CREATE INDEX boards_result_depth ON boards (material, result, depth);
+        ").map_err(|error| error.to_string())?;
+        conn.execute("
+            INSERT INTO meta (key, value) VALUES ('version', ?1), ('created_at', ?2)
+        ", params![env!("CARGO_PKG_VERSION"), created_at.to_string()]).map_err(|error| error.to_string())?;
+
+        Ok(summary)
+    }
+
+    // path のデータベースの駒構成とルール
+    // meta テーブルのない古いデータベースではファイル名 (Tablebase::get_database_name の形式) から求める
+    pub fn read_config(path: &str) -> Result<(Material, Rules), String> {
+        match Tablebase::read_meta(path) {
+            Ok(ref meta) if meta.contains_key("config") && meta.contains_key("rules") => {
+                Ok((Material::from_name(&meta["config"])?, Rules::from_name(&meta["rules"])?))
+            },
+            _ => {
+                // 以前の王将だけの駒構成のファイル名は ".sqlite3" になる
+                let file_name = Path::new(path).file_name().and_then(|name| name.to_str()).unwrap_or("");
+                Tablebase::parse_database_name(file_name.strip_suffix(".sqlite3").unwrap_or(file_name))
+            },
+        }
+    }
+
+    // path のデータベース (駒構成 config、ルール rules) の盤面を conn の boards テーブルに書き写し、読み込んだ盤面の数を返す
+    fn merge_database(conn: &Connection, path: &str, config: &Material, rules: &Rules) -> Result<u32, String> {
+        let hand_layout = HandLayout::from_pieces_with_capacities(&config.to_pieces(), rules.get_piece_set().get_capacities())?;
+
+        conn.execute("ATTACH DATABASE ?1 AS source", params![path]).map_err(|error| error.to_string())?;
+        let result = Tablebase::copy_boards(conn, config, rules, hand_layout.register()).map_err(|error| error.to_string());
+        conn.execute("DETACH DATABASE source", []).map_err(|error| error.to_string())?;
+
+        result
+    }
+
+    // Tablebase::merge_database で source として開いたデータベースの盤面を、盤面ごとの駒構成 (material 列) で書き写す
+    // 同じ盤面が複数のファイルにある場合は最初のものを使う
+    fn copy_boards(conn: &Connection, config: &Material, rules: &Rules, hand_layout: u8) -> rusqlite::Result<u32> {
+        // 古いデータベースには sfen 列がない
+        let has_sfen: bool = conn.query_row("
+            SELECT COUNT(*) > 0 FROM pragma_table_info('boards', 'source') WHERE name = 'sfen'
+        ", [], |row| row.get(0))?;
+        let sfen_column = if has_sfen {"sfen"} else {"NULL"};
+        let count: u32 = conn.query_row("SELECT COUNT(*) FROM source.boards", [], |row| row.get(0))?;
+
+        // 持ち駒を打てるルールでは駒構成が変わらないので、すべての盤面をファイルの駒構成としてまとめて書き写す
+        if rules.allow_drops {
+            conn.execute(&format!("
+                INSERT OR IGNORE INTO boards (material, board, result, depth, routes, is_good, sfen)
+                SELECT ?1, board, result, depth, routes, is_good, {} FROM source.boards
+            ", sfen_column), params![config.get_database_name(rules)])?;
+
+            return Ok(count);
+        }
+
+        // 持ち駒を打てないルールでは駒を取ると駒構成が小さくなり、その盤面も同じファイルに書き出されている
+        let rules_index = rules.register();
+
+        conn.execute_batch("BEGIN")?;
+
+        {
+            let mut select = conn.prepare(&format!("
+                SELECT board, result, depth, routes, is_good, {} FROM source.boards
+            ", sfen_column))?;
+            let mut insert = conn.prepare("
+                INSERT OR IGNORE INTO boards (material, board, result, depth, routes, is_good, sfen) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ")?;
+            let mut rows = select.query([])?;
+
+            while let Some(row) = rows.next()? {
+                let blob: Vec<u8> = row.get(0)?;
+                let material = Material::from_board(&Board::from_blob(&blob, hand_layout, rules_index)).get_database_name(rules);
+
+                insert.execute(params![
+                    material,
+                    blob,
+                    row.get::<_, Value>(1)?,
+                    row.get::<_, Value>(2)?,
+                    row.get::<_, Value>(3)?,
+                    row.get::<_, Value>(4)?,
+                    row.get::<_, Value>(5)?,
+                ])?;
+            }
+        }
+
+        conn.execute_batch("COMMIT")?;
+
+        Ok(count)
+    }
+
+    // boards テーブルに書き写した駒構成ごとに、駒構成とルールと盤面の数を tables テーブルに書き出す
+    fn write_tables(conn: &Connection) -> rusqlite::Result<()> {
+        let counts: Vec<(String, u32)> = {
+            let mut statement = conn.prepare("SELECT material, COUNT(*) FROM boards GROUP BY material")?;
+            let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+
+        for (material, count) in counts {
+            let (config, rules) = Tablebase::parse_database_name(&material).expect("Invalid material");
+
+            conn.execute("
+                INSERT INTO tables (material, config, rules, boards) VALUES (?1, ?2, ?3, ?4)
+            ", params![material, config.get_name(), rules.get_name(), count])?;
+        }
+
+        Ok(())
+    }
+
+    fn get_connection(&mut self, database_name: &str) -> Option<&Connection> {
+        let directory = &self.directory;
+
+        self.connections.entry(database_name.to_string()).or_insert_with(|| {
+            let path = Path::new(directory).join(format!("{}.sqlite3", database_name));
+
+            if !path.exists() {
+                return None;
+            }
+
+            Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).ok()
+        }).as_ref()
+    }
+
+    // 手番側から見た盤面の状態を返す。駒構成のデータベースが存在しない (まとめたデータベースに含まれない) 場合は None
+    pub fn probe(&mut self, board: &Board) -> Option<BoardState> {
+        let board = &board.normalize();
+
+        // 深さ0の盤面はデータベースに書き出されていない
+        if board.get_result() == BoardResult::Win {
+            return Some(BoardState {
+                result: BoardResult::Win,
+                depth: Some(0),
+                routes: Some(1),
+                is_good: Some(false),
+            });
+        }
+
+        let material = Material::from_board(board);
+        let rules = board.get_rules();
+        let database_name = material.get_database_name(rules);
+
+        if let Some((ref conn, ref materials)) = self.combined {
+            if !materials.contains(&database_name) {
+                return None;
+            }
+
+            return conn.query_row("
+                SELECT result, depth, routes, is_good FROM boards WHERE material = ?1 AND board = ?2
+            ", params![&database_name, &board.to_blob()], get_state).optional().unwrap();
+        }
+
+        if let Some(state) = self.probe_database(&database_name, board) {
+            return Some(state);
+        }
+
+        // 持ち駒を打てないルールでは駒を取ると駒構成が小さくなるが、その盤面は元の駒構成のデータベースにも書き出されている
+        if rules.allow_drops {
+            return None;
+        }
+
+        for larger_database_name in self.get_larger_database_names(&material, rules) {
+            if let Some(state) = self.probe_database(&larger_database_name, board) {
+                return Some(state);
+            }
+        }
+
+        None
+    }
+
+    // database_name のデータベースから盤面の状態を引く
+    fn probe_database(&mut self, database_name: &str, board: &Board) -> Option<BoardState> {
+        let conn = self.get_connection(database_name)?;
+
+        conn.query_row("
+            SELECT result, depth, routes, is_good FROM boards WHERE board = ?1
+        ", params![&board.to_blob()], get_state).optional().unwrap()
+    }
+
+    // directory にある、rules で解いた material より大きい駒構成のデータベースの名前 (駒構成の小さい順)
+    fn get_larger_database_names(&mut self, material: &Material, rules: &Rules) -> Vec<String> {
+        let directory = &self.directory;
+        let databases = self.databases.get_or_insert_with(|| {
+            let mut databases: Vec<(Material, Rules, String)> = fs::read_dir(directory).into_iter().flatten()
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().to_str().and_then(|name| name.strip_suffix(".sqlite3")).map(|name| name.to_string()))
+                .filter_map(|name| Tablebase::parse_database_name(&name).ok().map(|(config, rules)| (config, rules, name)))
+                .collect();
+            databases.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.2.cmp(&b.2)));
+            databases
+        });
+
+        databases.iter()
+            .filter(|&(config, config_rules, _)| config_rules == rules && config != material && config.contains(material))
+            .map(|(_, _, name)| name.clone())
+            .collect()
+    }
+
+    // 盤面の状態と手番側の最善手 (Board#legal_moves と同じく先手から見た座標) を返す
+    // 勝ちの盤面では最短で勝てる手、負けの盤面では最も長く粘れる手、
+    // 勝敗のつかない盤面では勝敗のつかない状態を保つ手を選ぶ
+    pub fn get_best_action(&mut self, board: &Board) -> Option<(BoardState, Option<Action>)> {
+        let state = self.probe(board)?;
+        let mut best: Option<(Action, BoardState)> = None;
+
+        for action in board.legal_moves() {
+            let transition_state = self.probe(&board.apply(&action))?;
+
+            let is_better = match best {
+                None => true,
+                Some((_, best_state)) => match state.result {
+                    BoardResult::Win => {
+                        transition_state.result == BoardResult::Lose && (
+                            best_state.result != BoardResult::Lose ||
+                            transition_state.depth < best_state.depth
+                        )
+                    },
+                    BoardResult::Lose => {
+                        transition_state.result == BoardResult::Win && transition_state.depth > best_state.depth
+                    },
+                    BoardResult::Unknown => {
+                        transition_state.result == BoardResult::Unknown && best_state.result != BoardResult::Unknown
+                    },
+                },
+            };
+
+            if is_better {
+                best = Some((action, transition_state));
+            }
+        }
+
+        Some((state, best.map(|(action, _)| action)))
+    }
+}
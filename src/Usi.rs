@@ -0,0 +1,224 @@
+use ::Board::{Action, Board, Coord};
+use ::Grid::Grid;
+use ::Piece::Piece;
+use ::Rules::{Rules, DEFAULT_RULES};
+use ::util::HandLayout;
+
+// SFEN形式の局面を手番つきの盤面 (先手から見た向き) に変換する
+pub fn parse_sfen(sfen: &str) -> Result<Board, String> {
+    parse_sfen_with_rules(sfen, &DEFAULT_RULES)
+}
+
+// 盤の大きさは rules に合わせる
+pub fn parse_sfen_with_rules(sfen: &str, rules: &Rules) -> Result<Board, String> {
+    let fields: Vec<&str> = sfen.split_whitespace().collect();
+
+    if fields.len() < 3 {
+        return Err(format!("Invalid SFEN: {}", sfen));
+    }
+
+    let mut board = Board::Empty().with_rules(rules);
+    let piece_set = rules.get_piece_set();
+    let mut counts = [0_u8; 7];
+    let mut kings = [0_u8; 2];
+
+    let ranks: Vec<&str> = fields[0].split('/').collect();
+
+    if ranks.len() != rules.height as usize {
+        return Err(format!("Board must have {} ranks: {}", rules.height, fields[0]));
+    }
+
+    for (y, rank) in ranks.iter().enumerate() {
+        let mut x = 0_u8;
+        let mut promoted = false;
+
+        for letter in rank.chars() {
+            if let Some(digit) = letter.to_digit(10) {
+                if promoted {
+                    return Err(format!("Invalid rank: {}", rank));
+                }
+                x = x.saturating_add(digit as u8);
+
+                if x > rules.width {
+                    return Err(format!("Invalid rank: {}", rank));
+                }
+                continue;
+            }
+
+            if letter == '+' {
+                if promoted {
+                    return Err(format!("Invalid rank: {}", rank));
+                }
+                promoted = true;
+                continue;
+            }
+
+            let piece = match piece_set.from_usi_char(letter) {
+                Some(piece) => piece,
+                None => return Err(format!("Unknown piece: {}", letter)),
+            };
+
+            if x >= rules.width || (promoted && !piece_set.is_promotable(piece)) {
+                return Err(format!("Invalid rank: {}", rank));
+            }
+
+            let player = if letter.is_ascii_uppercase() {0} else {1};
+
+            if piece == Piece::王将 {
+                kings[player as usize] += 1;
+            } else {
+                counts[piece.to_hand_index() as usize] += 1;
+            }
+
+            board = board.set_grid(x, y as u8, Grid {piece, player, promoted});
+            x += 1;
+            promoted = false;
+        }
+
+        if x != rules.width || promoted {
+            return Err(format!("Rank must have {} squares: {}", rules.width, rank));
+        }
+    }
+
+    if kings != [1, 1] {
+        return Err("Each player must have exactly one king".to_string());
+    }
+
+    let player = match fields[1] {
+        "b" => 0,
+        "w" => 1,
+        side => return Err(format!("Invalid side to move: {}", side)),
+    };
+
+    let mut hands: Vec<(u8, Piece, u8)> = Vec::new();
+
+    if fields[2] != "-" {
+        let mut number = 0_u8;
+
+        for letter in fields[2].chars() {
+            if let Some(digit) = letter.to_digit(10) {
+                number = number.saturating_mul(10).saturating_add(digit as u8);
+                continue;
+            }
+
+            let piece = match piece_set.from_usi_char(letter) {
+                Some(Piece::王将) | None => return Err(format!("Invalid piece in hand: {}", letter)),
+                Some(piece) => piece,
+            };
+            let count = if number == 0 {1} else {number};
+            let index = piece.to_hand_index() as usize;
+
+            counts[index] = counts[index].saturating_add(count);
+            hands.push((if letter.is_ascii_uppercase() {0} else {1}, piece, count));
+            number = 0;
+        }
+
+        if number != 0 {
+            return Err(format!("Invalid hands: {}", fields[2]));
+        }
+    }
+
+    // 持ち駒の表現は駒構成から求める (BoardMap::from_pieces_with_rules と同じ)
+    board = board.with_hand_layout(&HandLayout::from_counts_with_capacities(counts, piece_set.get_capacities())?);
+
+    for (player, piece, count) in hands {
+        board = board.add_hand(player, piece, count as i8);
+    }
+
+    if !board.is_valid() {
+        return Err(format!("Invalid position: {}", sfen));
+    }
+
+    Ok(board.with_side_to_move(player))
+}
+
+// 手番つきの盤面をSFEN形式に変換する
+pub fn to_sfen(board: &Board) -> String {
+    let (width, height) = board.get_size();
+    let piece_set = board.get_rules().get_piece_set();
+    let mut ranks: Vec<String> = Vec::with_capacity(height as usize);
+
+    for y in 0..height {
+        let mut rank = String::new();
+        let mut empties = 0;
+
+        for x in 0..width {
+            let grid = board.get_grid(x, y);
+
+            if grid.piece == Piece::Empty {
+                empties += 1;
+                continue;
+            }
+
+            if empties > 0 {
+                rank.push_str(&empties.to_string());
+                empties = 0;
+            }
+
+            if grid.promoted {
+                rank.push('+');
+            }
+
+            let letter = piece_set.get_usi_char(grid.piece);
+            rank.push(if grid.player == 0 {letter} else {letter.to_ascii_lowercase()});
+        }
+
+        if empties > 0 {
+            rank.push_str(&empties.to_string());
+        }
+
+        ranks.push(rank);
+    }
+
+    let mut hands = String::new();
+
+    for hand_player in 0..2 {
+        for i in 0..7 {
+            let piece = Piece::from_hand_index(i);
+            let count = board.get_hand_count(hand_player, piece);
+
+            if count == 0 {
+                continue;
+            }
+
+            if count > 1 {
+                hands.push_str(&count.to_string());
+            }
+
+            let letter = piece_set.get_usi_char(piece);
+            hands.push(if hand_player == 0 {letter} else {letter.to_ascii_lowercase()});
+        }
+    }
+
+    if hands.is_empty() {
+        hands.push('-');
+    }
+
+    format!("{} {} {} 1", ranks.join("/"), if board.side_to_move() == 0 {"b"} else {"w"}, hands)
+}
+
+// 先手から見た座標をUSIの筋・段の表記に変換する (筋は盤の右端から数える)
+fn coord_to_usi(board: &Board, coord: &Coord) -> String {
+    let (width, _) = board.get_size();
+    format!("{}{}", width - coord.x, (b'a' + coord.y) as char)
+}
+
+// 盤面での手番側の指し手 (Board#legal_moves と同じく先手から見た座標) をUSI形式に変換する
+pub fn action_to_usi(board: &Board, action: &Action) -> String {
+    match action {
+        Action::Move(mov) => {
+            let promote = mov.promote && !board.get_grid(mov.from.x, mov.from.y).promoted;
+            format!("{}{}{}", coord_to_usi(board, &mov.from), coord_to_usi(board, &mov.to), if promote {"+"} else {""})
+        },
+        Action::Drop(drop) => {
+            format!("{}*{}", board.get_rules().get_piece_set().get_usi_char(drop.piece), coord_to_usi(board, &drop.to))
+        },
+    }
+}
+
+// USI形式の指し手を合法手の中から探す
+pub fn parse_action(board: &Board, text: &str) -> Result<Action, String> {
+    board.legal_moves().into_iter()
+        .find(|action| action_to_usi(board, action) == text)
+        .ok_or_else(|| format!("Illegal move: {}", text))
+}
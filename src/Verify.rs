@@ -0,0 +1,86 @@
+use ::Board::{Board, BoardResult};
+use ::BoardMap::{BoardMap, BoardState};
+
+// データベースの整合性チェックで見つかった矛盾
+#[derive(Debug)]
+pub struct Violation {
+    pub board: Board,
+    // データベースに記録されている状態
+    pub state: BoardState,
+    // 遷移先の状態から求めた状態 (遷移先が見つからない場合は None)
+    pub expected: Option<BoardState>,
+    pub message: String,
+}
+
+fn check_state(board: &Board, state: &BoardState, expected: &BoardState) -> Option<Violation> {
+    let message = if state.result != expected.result {
+        format!("Result should be {:?} but {:?} is stored", expected.result, state.result)
+    } else if state.depth != expected.depth {
+        format!("Depth should be {:?} but {:?} is stored", expected.depth, state.depth)
+    } else if state.routes != expected.routes {
+        format!("Routes should be {:?} but {:?} is stored", expected.routes, state.routes)
+    } else if state.is_good != expected.is_good {
+        format!("is_good should be {:?} but {:?} is stored", expected.is_good, state.is_good)
+    } else {
+        return None;
+    };
+
+    Some(Violation {
+        board: *board,
+        state: *state,
+        expected: Some(*expected),
+        message,
+    })
+}
+
+// すべての盤面について、記録されている状態が遷移先の状態と矛盾しないかを調べる
+pub fn verify_map(board_map: &BoardMap) -> Vec<Violation> {
+    let mut violations: Vec<Violation> = Vec::new();
+
+    for (board, state) in board_map.map.iter() {
+        // 初期盤面 (BoardMap#place_pieces で勝敗が決まる盤面)
+        let initial_state = match board.get_result() {
+            BoardResult::Win => Some(BoardState {
+                result: BoardResult::Win,
+                depth: Some(0),
+                routes: Some(1),
+                is_good: Some(false),
+            }),
+            BoardResult::Lose => Some(BoardState {
+                result: BoardResult::Lose,
+                depth: Some(1),
+                routes: Some(1),
+                is_good: Some(false),
+            }),
+            BoardResult::Unknown => None,
+        };
+
+        let expected = match initial_state {
+            Some(initial_state) => initial_state,
+            None => match board_map.evaluate(board) {
+                Ok((expected, _)) => expected,
+                Err(transition) => {
+                    violations.push(Violation {
+                        board: *board,
+                        state: *state,
+                        expected: None,
+                        message: format!("Transition {:?} was not found in the database", transition),
+                    });
+                    continue;
+                },
+            },
+        };
+
+        if let Some(violation) = check_state(board, state, &expected) {
+            violations.push(violation);
+        }
+    }
+
+    violations
+}
+
+// path のデータベースを BoardMap::read で読み込み、盤面の数と矛盾の一覧を返す
+pub fn verify(path: &str) -> Result<(usize, Vec<Violation>), String> {
+    let board_map = BoardMap::read(path)?;
+    Ok((board_map.map.len(), verify_map(&board_map)))
+}
@@ -0,0 +1,43 @@
+#![allow(non_snake_case)]
+
+extern crate nine_grids_shogi_analyzer;
+
+use std::env;
+use std::process;
+use nine_grids_shogi_analyzer::Perft::{divide, PerftCount};
+use nine_grids_shogi_analyzer::Usi;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let depth = match args.first().and_then(|arg| arg.parse::<u8>().ok()) {
+        Some(depth) if depth >= 1 && args.len() > 1 => depth,
+        _ => {
+            println!("Usage: perft <depth> <sfen>");
+            println!("  e.g. perft 3 1k1/3/1K1 b G 1");
+            process::exit(1);
+        },
+    };
+
+    let board = match Usi::parse_sfen(&args[1..].join(" ")) {
+        Ok(board) => board,
+        Err(message) => {
+            println!("{}", message);
+            process::exit(1);
+        },
+    };
+
+    let mut total = PerftCount::default();
+
+    for (action, count) in divide(&board, depth) {
+        println!("{}: {}", Usi::action_to_usi(&board, &action), count.nodes);
+        total += count;
+    }
+
+    println!();
+    println!("Nodes: {}", total.nodes);
+    println!("Moves: {}", total.moves);
+    println!("Captures: {}", total.captures);
+    println!("Promotions: {}", total.promotions);
+    println!("Drops: {}", total.drops);
+}
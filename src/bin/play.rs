@@ -0,0 +1,142 @@
+#![allow(non_snake_case)]
+
+extern crate nine_grids_shogi_analyzer;
+
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+use nine_grids_shogi_analyzer::Board::BoardResult;
+use nine_grids_shogi_analyzer::BoardMap::BoardState;
+use nine_grids_shogi_analyzer::Material::Material;
+use nine_grids_shogi_analyzer::Rules::Rules;
+use nine_grids_shogi_analyzer::Tablebase::Tablebase;
+use nine_grids_shogi_analyzer::Usi;
+
+fn usage() -> ! {
+    println!("Usage: play [--gote] [--rules <rules>] <sfen>");
+    println!("  e.g. play 1k1/3/1K1 b G 1");
+    println!("Enter moves in USI notation (e.g. 2b2a+, P*1c). Type \"quit\" to exit.");
+    process::exit(1);
+}
+
+// 手番側から見た評価を表示用の文字列にする
+fn format_state(state: &BoardState, player: u8) -> String {
+    let mark = if player == 0 {"☗"} else {"☖"};
+    let depth = state.depth.unwrap_or(0).saturating_sub(1);
+
+    match state.result {
+        BoardResult::Win => format!("{} wins in {}", mark, depth),
+        BoardResult::Lose => format!("{} loses in {}", mark, depth),
+        BoardResult::Unknown => "draw".to_string(),
+    }
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let human = match args.iter().position(|arg| arg == "--gote") {
+        Some(index) => {
+            args.remove(index);
+            1
+        },
+        None => 0,
+    };
+
+    let rules = match args.iter().position(|arg| arg == "--rules") {
+        Some(index) if index + 1 < args.len() => {
+            let name = args.remove(index + 1);
+            args.remove(index);
+
+            match Rules::from_name(&name) {
+                Ok(rules) => rules,
+                Err(message) => {
+                    println!("{}", message);
+                    usage();
+                },
+            }
+        },
+        Some(_) => usage(),
+        None => Rules::default(),
+    };
+
+    if args.is_empty() {
+        usage();
+    }
+
+    let mut board = match Usi::parse_sfen_with_rules(&args.join(" "), &rules) {
+        Ok(board) => board,
+        Err(message) => {
+            println!("{}", message);
+            usage();
+        },
+    };
+    let mut tablebase = Tablebase::new("boards".to_string());
+
+    // 最初にデータベースがあることを確認しておく
+    // (持ち駒を打てないルールでは駒を取ると駒構成が変わるが、その盤面も元の駒構成のデータベースから引ける)
+    if tablebase.probe(&board).is_none() {
+        let material = Material::from_board(&board);
+        let rules_option = if rules == Rules::default() {String::new()} else {format!(" --rules {}", rules.get_name())};
+        println!("Database boards/{}.sqlite3 was not found. Generate it first with `cargo run --release {}{}`.", material.get_database_name(&rules), material, rules_option);
+        process::exit(1);
+    }
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop {
+        let player = board.side_to_move();
+        board.print();
+
+        let (state, best_action) = match tablebase.get_best_action(&board) {
+            Some(result) => result,
+            None => {
+                println!("The database does not contain this board or the boards after its moves.");
+                process::exit(1);
+            },
+        };
+        println!("Evaluation: {}", format_state(&state, player));
+
+        let best_action = match best_action {
+            Some(action) => action,
+            None => {
+                let (mark, opponent_mark) = if player == 0 {("☗", "☖")} else {("☖", "☗")};
+
+                // ステルスメイトの勝敗はルールによる
+                match state.result {
+                    BoardResult::Win => println!("{} has no legal moves. {} wins.", mark, mark),
+                    BoardResult::Lose => println!("{} has no legal moves. {} wins.", mark, opponent_mark),
+                    BoardResult::Unknown => println!("{} has no legal moves. Draw.", mark),
+                }
+                break;
+            },
+        };
+
+        if player == human {
+            print!("> ");
+            io::stdout().flush().unwrap();
+
+            let line = match lines.next() {
+                Some(line) => line.unwrap(),
+                None => break,
+            };
+            let text = line.trim();
+
+            if text == "quit" {
+                break;
+            }
+
+            match Usi::parse_action(&board, text) {
+                Ok(action) => {
+                    board = board.apply(&action);
+                },
+                Err(message) => {
+                    println!("{}", message);
+                    continue;
+                },
+            }
+        } else {
+            println!("Computer: {}", Usi::action_to_usi(&board, &best_action));
+            board = board.apply(&best_action);
+        }
+    }
+}
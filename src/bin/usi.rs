@@ -0,0 +1,158 @@
+#![allow(non_snake_case)]
+
+extern crate nine_grids_shogi_analyzer;
+
+use std::cmp;
+use std::io::{self, BufRead};
+use nine_grids_shogi_analyzer::Board::{Board, BoardResult};
+use nine_grids_shogi_analyzer::Rules::Rules;
+use nine_grids_shogi_analyzer::Search::Search;
+use nine_grids_shogi_analyzer::Tablebase::Tablebase;
+use nine_grids_shogi_analyzer::Usi;
+
+// データベースのない駒構成で探索する最大の手数
+const SEARCH_DEPTH: u8 = 9;
+
+// 読み筋として出力する最大の手数
+const MAX_PV_LENGTH: u8 = 32;
+
+fn print_score(result: BoardResult, depth: u8, pv: &[String]) {
+    let score = match result {
+        BoardResult::Win => format!("mate {}", depth.saturating_sub(1)),
+        BoardResult::Lose => format!("mate -{}", depth.saturating_sub(1)),
+        BoardResult::Unknown => "cp 0".to_string(),
+    };
+
+    if pv.is_empty() {
+        println!("info score {}", score);
+    } else {
+        println!("info depth {} score {} pv {}", pv.len(), score, pv.join(" "));
+    }
+}
+
+fn go(tablebase: &mut Tablebase, board: &Board) {
+    if board.legal_moves().is_empty() {
+        println!("bestmove resign");
+        return;
+    }
+
+    match tablebase.get_best_action(board) {
+        Some((state, Some(action))) => {
+            let bestmove = Usi::action_to_usi(board, &action);
+            let depth = state.depth.unwrap_or(0);
+            let mut pv = vec![bestmove.clone()];
+
+            // 勝敗が決まっている場合は王を取られる直前まで読み筋をたどる
+            let pv_length = if state.result == BoardResult::Unknown {1} else {cmp::min(depth.saturating_sub(1), MAX_PV_LENGTH)};
+            let mut board = board.apply(&action);
+
+            while (pv.len() as u8) < pv_length {
+                let action = match tablebase.get_best_action(&board) {
+                    Some((_, Some(action))) => action,
+                    _ => break,
+                };
+
+                pv.push(Usi::action_to_usi(&board, &action));
+                board = board.apply(&action);
+            }
+
+            print_score(state.result, depth, &pv);
+            println!("bestmove {}", bestmove);
+        },
+        _ => {
+            println!("info string no database for this position, searching up to {} plies", SEARCH_DEPTH);
+
+            let mut search = Search::with_tablebase(tablebase);
+            let (state, action) = search.search(board, SEARCH_DEPTH);
+            let action = action.unwrap();
+            let pv = search.get_principal_variation(board, MAX_PV_LENGTH as usize);
+            let mut pv_board = *board;
+            let pv: Vec<String> = pv.iter().map(|action| {
+                let text = Usi::action_to_usi(&pv_board, action);
+                pv_board = pv_board.apply(action);
+                text
+            }).collect();
+
+            print_score(state.result, state.depth.unwrap_or(0), &pv);
+            println!("info nodes {}", search.nodes);
+            println!("bestmove {}", Usi::action_to_usi(board, &action));
+        },
+    }
+}
+
+fn parse_position(tokens: &[&str], rules: &Rules) -> Result<Board, String> {
+    let moves_index = tokens.iter().position(|&token| token == "moves").unwrap_or(tokens.len());
+
+    let mut board = match tokens.first() {
+        Some(&"sfen") => Usi::parse_sfen_with_rules(&tokens[1..moves_index].join(" "), rules)?,
+        Some(&"startpos") => return Err("startpos is not defined for 3x3 shogi, use sfen".to_string()),
+        _ => return Err("Invalid position command".to_string()),
+    };
+
+    for &text in tokens.iter().skip(moves_index + 1) {
+        let action = Usi::parse_action(&board, text)?;
+        board = board.apply(&action);
+    }
+
+    Ok(board)
+}
+
+fn main() {
+    let mut directory = "boards".to_string();
+    let mut rules = Rules::default();
+    let mut tablebase = Tablebase::new(directory.clone());
+    let mut position: Option<Board> = None;
+
+    let stdin = io::stdin();
+
+    for line in stdin.lock().lines() {
+        let line = line.unwrap();
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        match tokens.first() {
+            Some(&"usi") => {
+                println!("id name nine-grids-shogi-analyzer {}", env!("CARGO_PKG_VERSION"));
+                println!("id author {}", env!("CARGO_PKG_AUTHORS"));
+                println!("option name BoardsDirectory type string default boards");
+                println!("option name Rules type string default <empty>");
+                println!("usiok");
+            },
+            Some(&"isready") => {
+                tablebase = Tablebase::new(directory.clone());
+                println!("readyok");
+            },
+            // setoption name BoardsDirectory value <path>
+            Some(&"setoption") if tokens.get(2) == Some(&"BoardsDirectory") && tokens.get(3) == Some(&"value") => {
+                directory = tokens[4..].join(" ");
+            },
+            // setoption name Rules value <name> (Rules::from_name の表記)
+            Some(&"setoption") if tokens.get(2) == Some(&"Rules") => {
+                let name = if tokens.get(3) == Some(&"value") {tokens[4..].join(" ")} else {String::new()};
+                rules = match Rules::from_name(&name) {
+                    Ok(rules) => rules,
+                    Err(message) => {
+                        println!("info string {}", message);
+                        Rules::default()
+                    },
+                };
+            },
+            Some(&"position") => {
+                position = match parse_position(&tokens[1..], &rules) {
+                    Ok(position) => Some(position),
+                    Err(message) => {
+                        println!("info string {}", message);
+                        None
+                    },
+                };
+            },
+            Some(&"go") => {
+                match position {
+                    Some(ref position) => go(&mut tablebase, position),
+                    None => println!("bestmove resign"),
+                }
+            },
+            Some(&"quit") => break,
+            _ => {},
+        }
+    }
+}
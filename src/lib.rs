@@ -4,4 +4,15 @@ pub mod util;
 pub mod Board;
 pub mod BoardMap;
 pub mod Grid;
+pub mod Material;
+pub mod Perft;
 pub mod Piece;
+pub mod PieceSet;
+pub mod Progress;
+pub mod Query;
+pub mod Rules;
+pub mod Search;
+pub mod Statistics;
+pub mod Tablebase;
+pub mod Usi;
+pub mod Verify;
@@ -1,288 +1,430 @@
 #![allow(non_snake_case)]
 
-extern crate integer_sqrt;
 extern crate fnv;
-
-mod util;
-mod Board;
-mod BoardMap;
-mod Grid;
-mod Piece;
-
-use fnv::FnvHashMap;
+extern crate nine_grids_shogi_analyzer;
+
+use fnv::FnvHashSet;
+use nine_grids_shogi_analyzer::{Board, BoardMap, Grid, Piece, Query, Usi, Verify};
+use nine_grids_shogi_analyzer::Material::{Material, KINGS_ONLY_NAME};
+use nine_grids_shogi_analyzer::Progress::TerminalProgress;
+use nine_grids_shogi_analyzer::Rules::Rules;
+use nine_grids_shogi_analyzer::Statistics::Statistics;
+use nine_grids_shogi_analyzer::Tablebase::Tablebase;
+use nine_grids_shogi_analyzer::util::HandLayout;
 use std::env;
 use std::fs;
+use std::path::Path;
+use std::process;
+use std::time::Instant;
+
+// 表示する矛盾の最大数
+const MAX_PRINTED_VIOLATIONS: usize = 10;
+// 表示する読み筋の最大の手数 (勝敗のつかない盤面の読み筋はここで打ち切る)
+const MAX_PRINCIPAL_VARIATION: usize = 20;
+
+fn verify(paths: Vec<String>) {
+    let mut is_ok = true;
+
+    for path in paths {
+        println!("Verifying {}...", path);
+
+        let (count, violations) = match Verify::verify(&path) {
+            Ok(result) => result,
+            Err(message) => {
+                println!("Cannot read {}: {}", path, message);
+                is_ok = false;
+                continue;
+            },
+        };
 
-fn main() {
-    fs::create_dir_all("boards").unwrap();
+        for violation in violations.iter().take(MAX_PRINTED_VIOLATIONS) {
+            println!("{}:", violation.message);
+            violation.board.print();
+            println!("Stored: {:?}", violation.state);
+            println!("Expected: {:?}", violation.expected);
+            println!();
+        }
 
-    let piece_config = match env::args().nth(1) {
-        Some(config) => config,
-        None => {
-            panic!("Please specify config as args");
+        if violations.len() > MAX_PRINTED_VIOLATIONS {
+            println!("... and {} more violations", violations.len() - MAX_PRINTED_VIOLATIONS);
+        }
+
+        println!("Verified {} boards: {} violations", count, violations.len());
+
+        if !violations.is_empty() {
+            is_ok = false;
+        }
+    }
+
+    if !is_ok {
+        process::exit(1);
+    }
+}
+
+// 駒構成の数字の並び (持ち駒の番号) または駒の名前の並び ("金銀" など) を駒構成にする
+// rules で表せない駒構成の場合は終了する
+fn parse_config(piece_config: &str, rules: &Rules) -> Material {
+    let material = if piece_config == KINGS_ONLY_NAME || piece_config.chars().all(|letter| letter.is_ascii_digit()) {
+        Material::from_name(piece_config)
+    } else {
+        Material::from_piece_names(&piece_config.chars().map(|letter| letter.to_string()).collect::<Vec<_>>(), rules.get_piece_set())
+    };
+
+    let material = match material {
+        Ok(material) => material,
+        Err(message) => {
+            println!("{}", message);
+            process::exit(1);
         },
     };
 
-    let pieces: Vec<Piece::Piece> = piece_config.chars().filter_map(|letter| {
-        match letter.to_digit(10) {
-            Some(digit) => {
-                Some(Piece::Piece::from_hand_index(digit as usize))
-            },
-            None => None,
-        }
-    }).collect();
+    if let Err(message) = HandLayout::from_pieces_with_capacities(&material.to_pieces(), rules.get_piece_set().get_capacities()) {
+        println!("Invalid config {}: {}", piece_config, message);
+        process::exit(1);
+    }
 
-    let cloned_pieces = pieces.clone();
-    let piece_indices = cloned_pieces.iter().map(|piece| piece.to_hand_index().to_string());
-    let filename = format!("boards/{}.sqlite3", piece_indices.collect::<Vec<_>>().concat());
+    material
+}
+
+// 駒構成の盤面を生成して解く
+// 解いた盤面と、盤面の数や打ち歩詰めになるため除いた遷移の数などの統計を返す (書き出しの時間は呼び出し側で設定する)
+fn solve(material: Material, rules: &Rules) -> (BoardMap::BoardMap, Statistics) {
+    let pieces = material.to_pieces();
+    println!("Generate boards from pieces {:?} with rules {:?}:", pieces, rules);
 
-    println!("Generate boards from pieces {:?}:", pieces);
+    let config_name = material.get_name();
+    let database_name = material.get_database_name(rules);
 
-    let mut board_map = BoardMap::BoardMap::from_pieces(pieces);
+    let mut progress = TerminalProgress::new();
+
+    let start = Instant::now();
+    let mut board_map = BoardMap::BoardMap::from_pieces_with_progress(pieces, rules, &mut progress);
+    let generation_time = start.elapsed();
 
     println!("Number of generated boards: {}", board_map.map.len());
     println!("Depth-0 Wins: {}", board_map.wins);
     println!("Depth-0 Loses: {}", board_map.loses);
 
-    let mut depth = 1;
-    let mut 打ち歩詰め_count = 0;
-
-    loop {
-        let mut current_map = BoardMap::BoardMap::Empty();
-
-        println!("Digging Depth-{}...", depth);
-
-        for (&board, &state) in board_map.map.iter() {
-            if state.result == Board::BoardResult::Unknown {
-                let transitions = board.get_possible_transitions();
-                let transition_count = transitions.len();
-
-                let mut is_all_win = true;
-                let mut is_any_lose = false;
-                let mut min_lose_depth = None;
-                let mut max_win_depth = None;
-                let mut win_routes = 0_u32;
-                let mut lose_routes = 0_u32;
-                // 非合法手の数
-                let mut win_0_count = 0_u16;
-
-                for transition in transitions {
-                    let transition_state = match board_map.map.get(&transition) {
-                        None => {
-                            println!("The following board was not found in map:");
-                            transition.print();
-                            println!("Transitioned from the following board:");
-                            board.print();
-                            panic!();
-                        },
-                        Some(state) => state,
-                    };
-
-                    match transition_state.result {
-                        Board::BoardResult::Win => {
-                            let new_depth = match transition_state.depth {
-                                None => {
-                                    println!("Depth of the following board was not set:");
-                                    transition.print();
-                                    panic!();
-                                },
-                                Some(depth) => depth,
-                            };
-
-                            let new_routes = match transition_state.routes {
-                                None => {
-                                    println!("Routes of the following board was not set:");
-                                    transition.print();
-                                    panic!();
-                                },
-                                Some(routes) => routes,
-                            };
-
-                            match max_win_depth {
-                                None => {
-                                    max_win_depth = Some(new_depth);
-                                    win_routes = new_routes;
-                                },
-                                Some(depth) => {
-                                    if new_depth == depth {
-                                        win_routes += new_routes;
-                                    } else if new_depth > depth {
-                                        max_win_depth = Some(new_depth);
-                                        win_routes = new_routes;
-                                    }
-                                },
-                            }
-
-                            if new_depth == 0 {
-                                win_0_count += 1;
-                            }
-                        },
-                        Board::BoardResult::Lose => {
-                            let new_depth = match transition_state.depth {
-                                None => {
-                                    println!("Depth of the following board was not set:");
-                                    transition.print();
-                                    panic!();
-                                },
-                                Some(depth) => depth,
-                            };
-
-                            let new_routes = match transition_state.routes {
-                                None => {
-                                    println!("Routes of the following board was not set:");
-                                    transition.print();
-                                    panic!();
-                                },
-                                Some(routes) => routes,
-                            };
-
-                            // 打ち歩詰め
-                            if new_depth == 1 && board.is_transition_打ち歩(transition) {
-                                打ち歩詰め_count += 1;
-                                win_0_count += 1;
-                                continue;
-                            }
-
-                            is_all_win = false;
-                            is_any_lose = true;
-
-                            match min_lose_depth {
-                                None => {
-                                    min_lose_depth = Some(new_depth);
-                                    lose_routes = new_routes;
-                                },
-                                Some(depth) => {
-                                    if new_depth == depth {
-                                        lose_routes += new_routes;
-                                    } else if new_depth < depth {
-                                        min_lose_depth = Some(new_depth);
-                                        lose_routes = new_routes;
-                                    }
-                                },
-                            }
-                        },
-                        Board::BoardResult::Unknown => {
-                            is_all_win = false;
-                        },
-                    }
-                }
-
-                if is_all_win {
-                    let max_win_depth = match max_win_depth {
-                        None => {
-                            println!("Transition of the following board was not found:");
-                            board.print();
-                            panic!();
-                        },
-                        Some(depth) => depth,
-                    };
-
-                    current_map.map.insert(board, BoardMap::BoardState {
-                        result: Board::BoardResult::Lose,
-                        depth: Some(max_win_depth + 1),
-                        routes: Some(win_routes),
-                        is_good: Some(false),
-                    });
-                    current_map.loses += 1;
-                } else if is_any_lose {
-                    let min_lose_depth = match min_lose_depth {
-                        None => {
-                            println!("Transition of the following board was not found:");
-                            board.print();
-                            panic!();
-                        },
-                        Some(depth) => depth,
-                    };
-
-                    current_map.map.insert(board, BoardMap::BoardState {
-                        result: Board::BoardResult::Win,
-                        depth: Some(min_lose_depth + 1),
-                        routes: Some(lose_routes),
-                        // 合法手の数が3つ以上かつ理想盤面の場合にフラグを立てる
-                        is_good: Some(transition_count as u16 - win_0_count >= 3 && board.is_good()),
-                    });
-                    current_map.wins += 1;
-                }
-            }
-        }
+    // 持ち駒を打てないルールで駒が取り除かれた盤面など、小さい駒構成の盤面は解いてあるデータベースから読み込む
+    let start = Instant::now();
+    let mut tablebase = Tablebase::new("boards".to_string());
+    let seeded_count = board_map.seed(&mut tablebase, &database_name);
+    let seed_time = start.elapsed();
+
+    if seeded_count > 0 {
+        println!("Seeded Boards from Existing Databases: {}", seeded_count);
+    }
 
-        if current_map.wins == 0 && current_map.loses == 0 {
-            break;
+    let start = Instant::now();
+    let 打ち歩詰め_count = board_map.solve_with_progress(&mut progress);
+    let solve_time = start.elapsed();
+
+    let mut statistics = Statistics::from_board_map(&config_name, &rules.get_name(), &board_map, 打ち歩詰め_count);
+    statistics.generation_time = generation_time;
+    statistics.seed_time = seed_time;
+    statistics.solve_time = solve_time;
+
+    (board_map, statistics)
+}
+
+// データベース filename と同じ場所に、拡張子を .json と .csv にした統計を書き出す
+fn write_statistics(statistics: &Statistics, filename: &str) {
+    let base_filename = filename.strip_suffix(".sqlite3").unwrap_or(filename);
+
+    fs::write(format!("{}.json", base_filename), statistics.to_json()).unwrap();
+    fs::write(format!("{}.csv", base_filename), statistics.to_csv()).unwrap();
+}
+
+// 深さごとの盤面の数と、それぞれの最初に見つかった盤面を表示する
+fn print_statistics(board_map: &BoardMap::BoardMap, statistics: &Statistics) {
+    let mut printed_examples: FnvHashSet<(Board::BoardResult, Option<u8>)> = FnvHashSet::default();
+
+    for (&board, &state) in board_map.map.iter() {
+        if !printed_examples.insert((state.result, state.depth)) {
+            continue;
         }
 
-        board_map.merge(current_map);
+        match (state.result, state.depth) {
+            (Board::BoardResult::Win, Some(depth)) => println!("Example of Move-{} Win Board:", depth),
+            (Board::BoardResult::Lose, Some(depth)) => println!("Example of Move-{} Lose Board:", depth),
+            _ => println!("Example of Unknown Board:"),
+        }
 
-        depth += 1;
+        board.print();
+        println!("{:?}", state);
     }
 
-    let mut win_map: FnvHashMap<u8, u32> = FnvHashMap::default();
-    let mut lose_map: FnvHashMap<u8, u32> = FnvHashMap::default();
-    let mut unknown_count = 0_u32;
+    println!("Total Boards: (wins: {}, loses: {}, unknowns: {})", statistics.get_win_count(), statistics.get_lose_count(), statistics.unknowns);
+    println!("Total Possible 打ち歩詰めs: {}", statistics.打ち歩詰め_count);
+    println!("Good Boards: {}", statistics.is_good_count);
 
-    for (&board, &state) in board_map.map.iter() {
-        if state.result == Board::BoardResult::Unknown {
-            if unknown_count == 0 {
-                println!("Example of Unknown Board:");
-                board.print();
-                println!("{:?}", state);
-            }
-            unknown_count += 1;
+    for (depth, (wins, loses)) in statistics.wins.iter().zip(statistics.loses.iter()).enumerate() {
+        println!("Move-{} Boards: (wins: {}, loses: {})", depth, wins, loses);
+    }
+}
+
+// 王将以外の駒が max_pieces 枚以下のすべての駒構成を解き、盤面の数と最長の手数を manifest に書き出す
+// データベースがすでにある駒構成は解き直さない
+fn batch(max_pieces: usize, rules: &Rules) {
+    let configs = Tablebase::enumerate_configs(max_pieces, rules.get_piece_set().get_capacities());
+    let manifest_filename = format!("boards/{}.csv", Tablebase::get_database_name("manifest", rules));
+    let mut manifest = String::from("config,boards,wins,loses,unknowns,max_win_depth,max_lose_depth\n");
+    let total_count = configs.len();
+
+    for (i, pieces) in configs.into_iter().enumerate() {
+        let material = Material::from_pieces(&pieces);
+        let config_name = material.get_name();
+        let filename = format!("boards/{}.sqlite3", material.get_database_name(rules));
+
+        if Path::new(&filename).exists() {
+            println!("[{}/{}] Skipping {}: already exists", i + 1, total_count, filename);
+        } else {
+            println!("[{}/{}] Solving {}:", i + 1, total_count, filename);
+
+            let (board_map, mut statistics) = solve(material, rules);
+
+            // 途中で止まった場合に解き終えたデータベースと区別できるように、書き終えてから名前を変える
+            let start = Instant::now();
+            let temporary_filename = format!("{}.tmp", filename);
+            let _ = fs::remove_file(&temporary_filename);
+            board_map.write_with_progress(temporary_filename.clone(), &mut TerminalProgress::new());
+            fs::rename(&temporary_filename, &filename).unwrap();
+            statistics.write_time = start.elapsed();
+
+            write_statistics(&statistics, &filename);
         }
 
-        match state.depth {
-            None => {},
-            Some(depth) => {
-                match state.result {
-                    Board::BoardResult::Win => {
-                        let wins = match win_map.get(&depth) {
-                            Some(&wins) => wins,
-                            None => 0,
-                        };
-
-                        if wins == 0 {
-                            println!("Example of Move-{} Win Board:", depth);
-                            board.print();
-                            println!("{:?}", state);
-                        }
-
-                        win_map.insert(depth, wins + 1);
-                    },
-                    Board::BoardResult::Lose => {
-                        let loses = match lose_map.get(&depth) {
-                            Some(&count) => count,
-                            None => 0,
-                        };
-
-                        if loses == 0 {
-                            println!("Example of Move-{} Lose Board:", depth);
-                            board.print();
-                            println!("{:?}", state);
-                        }
-
-                        lose_map.insert(depth, loses + 1);
-                    },
-                    _ => {},
-                }
+        let summary = match Tablebase::summarize(&filename) {
+            Ok(summary) => summary,
+            Err(message) => {
+                println!("Cannot read {}: {}", filename, message);
+                process::exit(1);
             },
-        }
+        };
+        let format_depth = |depth: Option<u8>| depth.map_or(String::new(), |depth| depth.to_string());
+
+        manifest.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            config_name,
+            summary.wins + summary.loses + summary.unknowns,
+            summary.wins,
+            summary.loses,
+            summary.unknowns,
+            format_depth(summary.max_win_depth),
+            format_depth(summary.max_lose_depth),
+        ));
+    }
+
+    fs::write(&manifest_filename, manifest).unwrap();
+    println!("Wrote the manifest of {} configs to {}", total_count, manifest_filename);
+}
+
+// boards/ の駒構成ごとのデータベースを output にまとめる
+fn merge(output: &str) {
+    let summary = match Tablebase::merge("boards", output) {
+        Ok(summary) => summary,
+        Err(message) => {
+            println!("Cannot merge databases into {}: {}", output, message);
+            process::exit(1);
+        },
+    };
+
+    for (path, message) in summary.skipped.iter() {
+        println!("Skipping {}: {}", path, message);
     }
 
-    println!("Total Boards: (wins: {}, loses: {}, unknowns: {})", board_map.wins, board_map.loses, unknown_count);
-    println!("Total Possible 打ち歩詰めs: {}", 打ち歩詰め_count);
+    for (material, count) in summary.materials.iter() {
+        println!("Merged {}: {} boards", material, count);
+    }
 
-    for i in 0..60 {
-        let wins = match win_map.get(&i) {
-            Some(&count) => count,
-            None => 0,
+    println!("Wrote {} configs to {}", summary.materials.len(), output);
+}
+
+// args から --name <value> を取り除いて value を返す
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == name)?;
+
+    if index + 1 >= args.len() {
+        println!("Please specify a value after {}", name);
+        process::exit(1);
+    }
+
+    let value = args.remove(index + 1);
+    args.remove(index);
+    Some(value)
+}
+
+// 駒構成のデータベースから条件に合う盤面を並べ、図と最善の読み筋を表示する
+// args は query に続く引数 (<駒構成> [--by depth|routes|moves] [--limit N] [--side win|lose|unknown] [--material <駒構成>] [--good])
+fn query(mut args: Vec<String>, rules: &Rules) {
+    let mut query = Query::Query::default();
+
+    if let Some(name) = take_option(&mut args, "--by") {
+        query.sort_key = match Query::SortKey::from_name(&name) {
+            Ok(sort_key) => sort_key,
+            Err(message) => {
+                println!("{}", message);
+                process::exit(1);
+            },
         };
-        let loses = match lose_map.get(&i) {
-            Some(&count) => count,
-            None => 0,
+    }
+
+    if let Some(limit) = take_option(&mut args, "--limit") {
+        query.limit = match limit.parse() {
+            Ok(limit) => limit,
+            Err(_) => {
+                println!("Invalid limit: {}", limit);
+                process::exit(1);
+            },
         };
+    }
+
+    if let Some(side) = take_option(&mut args, "--side") {
+        query.result = match side.as_str() {
+            "win" => Some(Board::BoardResult::Win),
+            "lose" => Some(Board::BoardResult::Lose),
+            "unknown" => Some(Board::BoardResult::Unknown),
+            _ => {
+                println!("Invalid side: {} (expected win, lose or unknown)", side);
+                process::exit(1);
+            },
+        };
+    }
+
+    if let Some(material) = take_option(&mut args, "--material") {
+        query.material = Some(parse_config(&material, rules).to_pieces());
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--good") {
+        args.remove(index);
+        query.is_good = Some(true);
+    }
+
+    let piece_config = match args.first() {
+        Some(config) => config.clone(),
+        None => {
+            println!("Please specify config after query");
+            process::exit(1);
+        },
+    };
+
+    let filename = format!("boards/{}.sqlite3", parse_config(&piece_config, rules).get_database_name(rules));
+
+    if !Path::new(&filename).exists() {
+        println!("Database {} was not found", filename);
+        process::exit(1);
+    }
+
+    let board_map = match BoardMap::BoardMap::read(&filename) {
+        Ok(board_map) => board_map,
+        Err(message) => {
+            println!("Cannot read {}: {}", filename, message);
+            process::exit(1);
+        },
+    };
+    let results = Query::query(&board_map, &query);
+    let mut tablebase = Tablebase::new("boards".to_string());
+
+    println!("{} of {} boards in {} sorted by {:?}:", results.len(), board_map.map.len(), filename, query.sort_key);
+    println!();
+
+    for (i, result) in results.iter().enumerate() {
+        let state = &result.state;
+        let evaluation = match (state.result, state.depth) {
+            (Board::BoardResult::Win, Some(depth)) => format!("Win in {}", depth.saturating_sub(1)),
+            (Board::BoardResult::Lose, Some(depth)) => format!("Lose in {}", depth.saturating_sub(1)),
+            _ => "Unknown".to_string(),
+        };
+
+        println!(
+            "#{} {} (depth: {:?}, routes: {:?}, legal moves: {}, is_good: {:?})",
+            i + 1, evaluation, state.depth, state.routes, result.moves, state.is_good,
+        );
+        result.board.print();
+        println!("sfen {}", Usi::to_sfen(&result.board));
+
+        let mut board = result.board;
+        let variation: Vec<String> = Query::get_principal_variation(&mut tablebase, &board, MAX_PRINCIPAL_VARIATION).iter().map(|action| {
+            let usi = Usi::action_to_usi(&board, action);
+            board = board.apply(action);
+            usi
+        }).collect();
+
+        println!("pv {}", variation.join(" "));
+        println!();
+    }
+}
+
+fn main() {
+    if env::args().nth(1) == Some("verify".to_string()) {
+        verify(env::args().skip(2).collect());
+        return;
+    }
+
+    fs::create_dir_all("boards").unwrap();
+
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    // --rules <name> (Rules::from_name の表記)
+    let rules = match args.iter().position(|arg| arg == "--rules") {
+        Some(index) if index + 1 < args.len() => {
+            let name = args.remove(index + 1);
+            args.remove(index);
+
+            match Rules::from_name(&name) {
+                Ok(rules) => rules,
+                Err(message) => {
+                    println!("Invalid rules {}: {}", name, message);
+                    process::exit(1);
+                },
+            }
+        },
+        Some(_) => {
+            println!("Please specify rules after --rules");
+            process::exit(1);
+        },
+        None => Rules::default(),
+    };
+
+    // merge [まとめたデータベースのファイル名]
+    if args.first().map(|arg| arg.as_str()) == Some("merge") {
+        merge(args.get(1).map_or("boards/combined.sqlite3", |output| output.as_str()));
+        return;
+    }
 
-        println!("Move-{} Boards: (wins: {}, loses: {})", i, wins, loses);
+    // query <駒構成> [オプション]
+    if args.first().map(|arg| arg.as_str()) == Some("query") {
+        query(args.split_off(1), &rules);
+        return;
     }
 
+    // batch <最大の駒の数>
+    if args.first().map(|arg| arg.as_str()) == Some("batch") {
+        match args.get(1).and_then(|max_pieces| max_pieces.parse().ok()) {
+            Some(max_pieces) => batch(max_pieces, &rules),
+            None => {
+                println!("Please specify the maximum number of pieces after batch");
+                process::exit(1);
+            },
+        }
+        return;
+    }
+
+    let piece_config = match args.first() {
+        Some(config) => config.clone(),
+        None => {
+            panic!("Please specify config as args");
+        },
+    };
+
+    let material = parse_config(&piece_config, &rules);
+
+    let filename = format!("boards/{}.sqlite3", material.get_database_name(&rules));
+
+    let (board_map, mut statistics) = solve(material, &rules);
+
+    print_statistics(&board_map, &statistics);
+
     let board = Board::Board::Empty();
     let board = board.set_grid(1, 0, Grid::Grid {piece: Piece::Piece::王将, player: 0, promoted: false});
     let board = board.set_grid(1, 1, Grid::Grid {piece: Piece::Piece::飛車, player: 0, promoted: false});
@@ -313,5 +455,9 @@ fn main() {
 
     println!("Writing out to {}:", filename);
 
-    board_map.write(filename.to_string());
+    let start = Instant::now();
+    board_map.write_with_progress(filename.to_string(), &mut TerminalProgress::new());
+    statistics.write_time = start.elapsed();
+
+    write_statistics(&statistics, &filename);
 }
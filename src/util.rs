@@ -1,6 +1,9 @@
 extern crate integer_sqrt;
 
 use self::integer_sqrt::IntegerSquareRoot;
+use std::cmp::max;
+use std::sync::{Mutex, OnceLock};
+use ::Piece::Piece;
 
 // ある駒についての先手後手の持ち駒の数を表す
 #[derive(PartialEq, Eq, Debug)]
@@ -27,8 +30,8 @@ pub fn sqrt_ceil(n: u16) -> u16 {
 // ここからiを解くと i = (2n + 1 - sqrt(4n^2 + 4n + 1 - 8N)) / 2
 // つまりNにインデックスを代入して求められるiを切り捨てたものがaの値となる。
 pub fn hand_data_to_hand_info(hand_data: u8, max_pieces: u8) -> HandInfo {
-    assert!(hand_data < (max_pieces + 1) * (max_pieces + 2) / 2);
     let size: u16 = (max_pieces + 1) as u16;
+    assert!((hand_data as u16) < size * (size + 1) / 2);
     let i: u16 = (2 * size + 1 - sqrt_ceil(4 * size * size + 4 * size + 1 - 8 * (hand_data as u16))) / 2;
     #[allow(non_snake_case)]
     let N: u16 = (size * 2 - i + 1) * i / 2;
@@ -41,7 +44,265 @@ pub fn hand_data_to_hand_info(hand_data: u8, max_pieces: u8) -> HandInfo {
 
 pub fn hand_info_to_hand_data(hand_info: HandInfo, max_pieces: u8) -> u8 {
     assert!(hand_info.first + hand_info.second <= max_pieces);
-    let size = max_pieces + 1;
+    let size = max_pieces as u16 + 1;
+    let first = hand_info.first as u16;
 
-    (size * 2 - hand_info.first + 1) * hand_info.first / 2 + hand_info.second
+    ((size * 2 - first + 1) * first / 2 + hand_info.second as u16) as u8
 }
+
+// Board に 1 バイトのインデックスで持たせる値の一覧
+// インデックス 0 は既定値を表し、i (> 0) は i - 1 番目に登録した値を表す
+pub struct Registry<T: 'static> {
+    slots: [OnceLock<T>; 255],
+    lock: Mutex<()>,
+}
+
+impl<T: Copy + PartialEq> Default for Registry<T> {
+    fn default() -> Registry<T> {
+        Registry::new()
+    }
+}
+
+impl<T: Copy + PartialEq> Registry<T> {
+    pub const fn new() -> Registry<T> {
+        Registry {
+            slots: [const { OnceLock::new() }; 255],
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn register(&self, value: &T, default: &T) -> u8 {
+        if value == default {
+            return 0;
+        }
+
+        let _lock = self.lock.lock().unwrap();
+
+        for (i, slot) in self.slots.iter().enumerate() {
+            match slot.get() {
+                Some(registered) if registered == value => return (i + 1) as u8,
+                Some(_) => {},
+                None => {
+                    assert!(slot.set(*value).is_ok());
+                    return (i + 1) as u8;
+                },
+            }
+        }
+
+        panic!("Too many registered values");
+    }
+
+    // 登録した値のうち predicate に合う最初のもの
+    pub fn find<F: Fn(&T) -> bool>(&'static self, predicate: F) -> Option<&'static T> {
+        self.slots.iter().map_while(|slot| slot.get()).find(|&value| predicate(value))
+    }
+
+    pub fn get(&'static self, index: u8, default: &'static T) -> &'static T {
+        match index {
+            0 => default,
+            _ => self.slots[index as usize - 1].get().expect("Unknown index"),
+        }
+    }
+}
+
+// 持ち駒の種類ごとの最大枚数の既定値
+// 駒構成の枚数がこれ以下の場合は持ち駒の表現が変わらないので、以前に書き出したデータベースもそのまま読める
+pub const DEFAULT_HAND_CAPACITIES: [u8; 7] = [2, 2, 4, 4, 4, 4, 7];
+// 持ち駒の種類ごとの最大枚数の上限
+pub const MAX_HAND_CAPACITY: u8 = 15;
+
+// 最大枚数が MAX_HAND_CAPACITY の持ち駒の hand_data の種類数
+const HAND_DATA_COUNT: usize = (MAX_HAND_CAPACITY as usize + 1) * (MAX_HAND_CAPACITY as usize + 2) / 2;
+
+// Board#hands での持ち駒の種類ごとの最大枚数・ビット数・ビット位置
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct HandLayout {
+    pub capacities: [u8; 7],
+    pub sizes: [u32; 7],
+    pub offsets: [u32; 7],
+}
+
+// 既定の最大枚数での HandLayout (Board#hand_layout が 0 の盤面)
+pub const DEFAULT_HAND_LAYOUT: HandLayout = HandLayout {
+    capacities: DEFAULT_HAND_CAPACITIES,
+    sizes: [3, 3, 4, 4, 4, 4, 6],
+    offsets: [0, 3, 6, 10, 14, 18, 22],
+};
+
+// HandLayout#register で登録した既定以外の HandLayout
+static HAND_LAYOUTS: Registry<HandLayout> = Registry::new();
+
+impl HandLayout {
+    pub fn new(capacities: [u8; 7]) -> Result<HandLayout, String> {
+        let mut sizes = [0; 7];
+        let mut offsets = [0; 7];
+        let mut offset = 0;
+
+        for hand_type in 0..7 {
+            let capacity = capacities[hand_type];
+
+            if capacity > MAX_HAND_CAPACITY {
+                return Err(format!("Too many {}: {} (at most {})", Piece::from_hand_index(hand_type).to_char(), capacity, MAX_HAND_CAPACITY));
+            }
+
+            let count = (capacity as u32 + 1) * (capacity as u32 + 2) / 2;
+            sizes[hand_type] = 32 - (count - 1).leading_zeros();
+            offsets[hand_type] = offset;
+            offset += sizes[hand_type];
+        }
+
+        if offset > 32 {
+            return Err(format!("Hands of {:?} need {} bits, which exceeds 32 bits", capacities, offset));
+        }
+
+        Ok(HandLayout {
+            capacities,
+            sizes,
+            offsets,
+        })
+    }
+
+    // 駒の種類ごとの枚数から、それらをすべて持ち駒にできる HandLayout を作る
+    pub fn from_counts(counts: [u8; 7]) -> Result<HandLayout, String> {
+        HandLayout::from_counts_with_capacities(counts, DEFAULT_HAND_CAPACITIES)
+    }
+
+    // 最大枚数は capacities (PieceSet#get_capacities など) を下回らないようにする
+    pub fn from_counts_with_capacities(counts: [u8; 7], capacities: [u8; 7]) -> Result<HandLayout, String> {
+        let mut capacities = capacities;
+
+        for hand_type in 0..7 {
+            capacities[hand_type] = max(capacities[hand_type], counts[hand_type]);
+        }
+
+        HandLayout::new(capacities)
+    }
+
+    pub fn from_pieces(pieces: &[Piece]) -> Result<HandLayout, String> {
+        HandLayout::from_pieces_with_capacities(pieces, DEFAULT_HAND_CAPACITIES)
+    }
+
+    pub fn from_pieces_with_capacities(pieces: &[Piece], capacities: [u8; 7]) -> Result<HandLayout, String> {
+        let mut counts = [0_u8; 7];
+
+        for piece in pieces {
+            let index = piece.to_hand_index() as usize;
+            counts[index] = counts[index].saturating_add(1);
+        }
+
+        HandLayout::from_counts_with_capacities(counts, capacities)
+    }
+
+    // Board#hand_layout に格納するインデックス
+    pub fn register(&self) -> u8 {
+        HAND_LAYOUTS.register(self, &DEFAULT_HAND_LAYOUT)
+    }
+
+    pub fn get(index: u8) -> &'static HandLayout {
+        HAND_LAYOUTS.get(index, &DEFAULT_HAND_LAYOUT)
+    }
+
+    fn get_hand_data(&self, hands: u32, hand_type: usize) -> usize {
+        ((hands >> self.offsets[hand_type]) & ((1 << self.sizes[hand_type]) - 1)) as usize
+    }
+
+    // hands の hand_type 番目の駒の (先手, 後手) の持ち駒の数
+    pub fn get_hand_counts(&self, hands: u32, hand_type: usize) -> (u8, u8) {
+        HAND_INFOS[self.capacities[hand_type] as usize][self.get_hand_data(hands, hand_type)]
+    }
+
+    // hands の hand_type 番目の駒について、player の持ち駒の数に count を足す
+    // 後手の持ち駒は hand_data にそのまま足せばよく、先手の持ち駒は hand_info_to_hand_data と同じ計算で求める
+    pub fn add_hand(&self, hands: u32, hand_type: usize, player: u8, count: i8) -> u32 {
+        let hand_data = self.get_hand_data(hands, hand_type);
+        let max_pieces = self.capacities[hand_type];
+        let (first, second) = HAND_INFOS[max_pieces as usize][hand_data];
+        let max_pieces = max_pieces as i32;
+        let count = count as i32;
+
+        let new_hand_data = match player {
+            0 => {
+                let new_first = first as i32 + count;
+                assert!(new_first >= 0 && new_first + second as i32 <= max_pieces);
+                ((max_pieces + 1) * 2 - new_first + 1) as u32 * new_first as u32 / 2 + second as u32
+            },
+            1 => {
+                let new_second = second as i32 + count;
+                assert!(new_second >= 0 && first as i32 + new_second <= max_pieces);
+                (hand_data as i32 + count) as u32
+            },
+            _ => panic!(),
+        };
+
+        (hands & !(((1 << self.sizes[hand_type]) - 1) << self.offsets[hand_type])) | (new_hand_data << self.offsets[hand_type])
+    }
+
+    // 先手と後手の持ち駒を入れ替える
+    pub fn reverse_hands(&self, hands: u32) -> u32 {
+        let mut new_hands = 0;
+
+        for hand_type in 0..7 {
+            let hand_data = self.get_hand_data(hands, hand_type);
+            new_hands |= (REVERSED_HAND_DATA[self.capacities[hand_type] as usize][hand_data] as u32) << self.offsets[hand_type];
+        }
+
+        new_hands
+    }
+}
+
+impl Default for HandLayout {
+    fn default() -> HandLayout {
+        DEFAULT_HAND_LAYOUT
+    }
+}
+
+// 最大枚数と hand_data から (先手, 後手) の持ち駒の数を引く表を作る (hand_data_to_hand_info と同じ並び)
+const fn build_hand_infos() -> [[(u8, u8); HAND_DATA_COUNT]; MAX_HAND_CAPACITY as usize + 1] {
+    let mut table = [[(0, 0); HAND_DATA_COUNT]; MAX_HAND_CAPACITY as usize + 1];
+    let mut max_pieces = 0;
+
+    while max_pieces <= MAX_HAND_CAPACITY {
+        let mut hand_data = 0;
+        let mut first = 0;
+
+        while first <= max_pieces {
+            let mut second = 0;
+
+            while first + second <= max_pieces {
+                table[max_pieces as usize][hand_data] = (first, second);
+                hand_data += 1;
+                second += 1;
+            }
+
+            first += 1;
+        }
+
+        max_pieces += 1;
+    }
+
+    table
+}
+
+// 先手と後手の持ち駒の数を入れ替えた hand_data を引く表を作る
+const fn build_reversed_hand_data() -> [[u8; HAND_DATA_COUNT]; MAX_HAND_CAPACITY as usize + 1] {
+    let mut table = [[0; HAND_DATA_COUNT]; MAX_HAND_CAPACITY as usize + 1];
+    let mut max_pieces = 0;
+
+    while max_pieces <= MAX_HAND_CAPACITY as usize {
+        let size = max_pieces + 1;
+        let mut hand_data = 0;
+
+        while hand_data < size * (size + 1) / 2 {
+            let (first, second) = HAND_INFOS[max_pieces][hand_data];
+            table[max_pieces][hand_data] = ((size * 2 - second as usize + 1) * second as usize / 2 + first as usize) as u8;
+            hand_data += 1;
+        }
+
+        max_pieces += 1;
+    }
+
+    table
+}
+
+const HAND_INFOS: [[(u8, u8); HAND_DATA_COUNT]; MAX_HAND_CAPACITY as usize + 1] = build_hand_infos();
+const REVERSED_HAND_DATA: [[u8; HAND_DATA_COUNT]; MAX_HAND_CAPACITY as usize + 1] = build_reversed_hand_data();
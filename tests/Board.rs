@@ -1,8 +1,11 @@
 extern crate nine_grids_shogi_analyzer;
 
-use nine_grids_shogi_analyzer::Board::{Board, BoardResult, PieceMove, Coord};
+use nine_grids_shogi_analyzer::Board::{Board, BoardResult, PieceMove, PieceDrop, Coord, Action};
 use nine_grids_shogi_analyzer::Piece::{Piece};
 use nine_grids_shogi_analyzer::Grid::{Grid};
+use nine_grids_shogi_analyzer::BoardMap::{BoardMap};
+use nine_grids_shogi_analyzer::Rules::{Rules};
+use std::collections::HashSet;
 
 #[test]
 fn board_get_possible_moves_test() {
@@ -11,7 +14,7 @@ fn board_get_possible_moves_test() {
      *  ・ ・ ・
      *  ・ 王 ・
      */
-    let board = Board {grids: 0, hands: 0, player: true};
+    let board = Board {grids: 0, hands: 0, player: false, hand_layout: 0, rules: 0};
     let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
 
     let moves = board.get_possible_moves();
@@ -28,7 +31,7 @@ fn board_get_possible_moves_test() {
      *  ・ 金 ・
      *  ・ 王 ・
      */
-    let board = Board {grids: 0, hands: 0, player: true};
+    let board = Board {grids: 0, hands: 0, player: false, hand_layout: 0, rules: 0};
     let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
     let board = board.set_grid(1, 1, Grid {piece: Piece::金将, player: 0, promoted: false});
     let moves = board.get_possible_moves();
@@ -39,7 +42,7 @@ fn board_get_possible_moves_test() {
      *  ・ 香 ・
      *  ・ 王 ・
      */
-    let board = Board {grids: 0, hands: 0, player: true};
+    let board = Board {grids: 0, hands: 0, player: false, hand_layout: 0, rules: 0};
     let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
     let board = board.set_grid(1, 1, Grid {piece: Piece::香車, player: 0, promoted: false});
     let moves = board.get_possible_moves();
@@ -50,7 +53,7 @@ fn board_get_possible_moves_test() {
      *  ・ 銀 ・
      *  ・ 王 ・
      */
-    let board = Board {grids: 0, hands: 0, player: true};
+    let board = Board {grids: 0, hands: 0, player: false, hand_layout: 0, rules: 0};
     let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
     let board = board.set_grid(1, 1, Grid {piece: Piece::銀将, player: 0, promoted: false});
     let moves = board.get_possible_moves();
@@ -61,7 +64,7 @@ fn board_get_possible_moves_test() {
      *  ・ ・ ・
      *  ・ 王 ・
      */
-    let board = Board {grids: 0, hands: 0, player: true};
+    let board = Board {grids: 0, hands: 0, player: false, hand_layout: 0, rules: 0};
     let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
     let board = board.set_grid(1, 0, Grid {piece: Piece::銀将, player: 0, promoted: false});
     let moves = board.get_possible_moves();
@@ -72,7 +75,7 @@ fn board_get_possible_moves_test() {
      *  ・ 全 ・
      *  ・ 王 ・
      */
-    let board = Board {grids: 0, hands: 0, player: true};
+    let board = Board {grids: 0, hands: 0, player: false, hand_layout: 0, rules: 0};
     let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
     let board = board.set_grid(1, 1, Grid {piece: Piece::銀将, player: 0, promoted: true});
     let moves = board.get_possible_moves();
@@ -83,7 +86,7 @@ fn board_get_possible_moves_test() {
      *  ・v歩 ・
      *  ・ 王 ・
      */
-    let board = Board {grids: 0, hands: 0, player: true};
+    let board = Board {grids: 0, hands: 0, player: false, hand_layout: 0, rules: 0};
     let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
     let board = board.set_grid(1, 1, Grid {piece: Piece::歩兵, player: 1, promoted: false});
     let moves = board.get_possible_moves();
@@ -94,7 +97,7 @@ fn board_get_possible_moves_test() {
      *  ・v歩 ・
      *  ・ 王 角
      */
-    let board = Board {grids: 0, hands: 0, player: true};
+    let board = Board {grids: 0, hands: 0, player: false, hand_layout: 0, rules: 0};
     let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
     let board = board.set_grid(1, 1, Grid {piece: Piece::歩兵, player: 1, promoted: false});
     let board = board.set_grid(2, 2, Grid {piece: Piece::角行, player: 0, promoted: false});
@@ -106,7 +109,7 @@ fn board_get_possible_moves_test() {
      *  ・v歩 ・
      *  ・ 王 馬
      */
-    let board = Board {grids: 0, hands: 0, player: true};
+    let board = Board {grids: 0, hands: 0, player: false, hand_layout: 0, rules: 0};
     let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
     let board = board.set_grid(1, 1, Grid {piece: Piece::歩兵, player: 1, promoted: false});
     let board = board.set_grid(2, 2, Grid {piece: Piece::角行, player: 0, promoted: true});
@@ -118,7 +121,7 @@ fn board_get_possible_moves_test() {
      * v歩v歩v歩
      *  王 桂 ・
      */
-    let board = Board {grids: 0, hands: 0, player: true};
+    let board = Board {grids: 0, hands: 0, player: false, hand_layout: 0, rules: 0};
     let board = board.set_grid(0, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
     let board = board.set_grid(0, 1, Grid {piece: Piece::歩兵, player: 1, promoted: false});
     let board = board.set_grid(1, 1, Grid {piece: Piece::歩兵, player: 1, promoted: false});
@@ -132,7 +135,7 @@ fn board_get_possible_moves_test() {
      *  ・ 歩 歩
      * v王 ・ ・
      */
-    let board = Board {grids: 0, hands: 0, player: true};
+    let board = Board {grids: 0, hands: 0, player: false, hand_layout: 0, rules: 0};
     let board = board.set_grid(0, 0, Grid {piece: Piece::銀将, player: 0, promoted: false});
     let board = board.set_grid(1, 0, Grid {piece: Piece::金将, player: 0, promoted: false});
     let board = board.set_grid(2, 0, Grid {piece: Piece::王将, player: 0, promoted: false});
@@ -150,7 +153,7 @@ fn board_get_result_test() {
      *  ・ ・ ・
      *  ・ 王 ・
      */
-    let board = Board {grids: 0, hands: 0, player: true};
+    let board = Board {grids: 0, hands: 0, player: false, hand_layout: 0, rules: 0};
     let board = board.set_grid(1, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
     let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
     assert_eq!(board.get_result(), BoardResult::Unknown);
@@ -160,7 +163,7 @@ fn board_get_result_test() {
      *  ・v王 ・
      *  ・ 王 ・
      */
-    let board = Board {grids: 0, hands: 0, player: true};
+    let board = Board {grids: 0, hands: 0, player: false, hand_layout: 0, rules: 0};
     let board = board.set_grid(1, 1, Grid {piece: Piece::王将, player: 1, promoted: false});
     let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
     assert_eq!(board.get_result(), BoardResult::Win);
@@ -170,7 +173,7 @@ fn board_get_result_test() {
      *  ・ ・ ・
      *  ・ 王 桂
      */
-    let board = Board {grids: 0, hands: 0, player: true};
+    let board = Board {grids: 0, hands: 0, player: false, hand_layout: 0, rules: 0};
     let board = board.set_grid(1, 1, Grid {piece: Piece::王将, player: 1, promoted: false});
     let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
     let board = board.set_grid(2, 2, Grid {piece: Piece::桂馬, player: 0, promoted: false});
@@ -181,7 +184,7 @@ fn board_get_result_test() {
      *  ・ 歩 歩
      * v王 ・ ・
      */
-    let board = Board {grids: 0, hands: 0, player: true};
+    let board = Board {grids: 0, hands: 0, player: false, hand_layout: 0, rules: 0};
     let board = board.set_grid(0, 0, Grid {piece: Piece::銀将, player: 0, promoted: false});
     let board = board.set_grid(1, 0, Grid {piece: Piece::金将, player: 0, promoted: false});
     let board = board.set_grid(2, 0, Grid {piece: Piece::王将, player: 0, promoted: false});
@@ -196,7 +199,7 @@ fn board_get_result_test() {
      * v王 ・ ・
      * ☗歩
      */
-    let board = Board {grids: 0, hands: 0, player: true};
+    let board = Board {grids: 0, hands: 0, player: false, hand_layout: 0, rules: 0};
     let board = board.set_grid(0, 0, Grid {piece: Piece::銀将, player: 0, promoted: false});
     let board = board.set_grid(1, 0, Grid {piece: Piece::金将, player: 0, promoted: false});
     let board = board.set_grid(2, 0, Grid {piece: Piece::王将, player: 0, promoted: false});
@@ -223,3 +226,272 @@ fn board_get_result_test() {
     let board = board.add_hand(0, Piece::歩兵, 1);
     assert_eq!(board.get_result(), BoardResult::Lose);
 }
+
+#[test]
+fn board_get_legal_actions_test() {
+    /*
+     * v王 ・ 金
+     *  ・ ・ ・
+     *  ・ 金 王
+     * ☗歩
+     */
+    let board = Board::Empty();
+    let board = board.set_grid(0, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.set_grid(2, 0, Grid {piece: Piece::金将, player: 0, promoted: false});
+    let board = board.set_grid(1, 2, Grid {piece: Piece::金将, player: 0, promoted: false});
+    let board = board.set_grid(2, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
+    let board = board.add_hand(0, Piece::歩兵, 1);
+
+    let actions = board.get_legal_actions();
+
+    // 打ち歩詰め (どこに打っても後手は指せる手がなくなる)
+    assert!(board.apply_action(&Action::Drop(PieceDrop {piece: Piece::歩兵, to: Coord {x: 0, y: 1}})).is_mated());
+    assert!(!actions.contains(&Action::Drop(PieceDrop {piece: Piece::歩兵, to: Coord {x: 0, y: 1}})));
+    assert!(!actions.iter().any(|action| matches!(action, Action::Drop(_))));
+    assert!(actions.contains(&Action::Move(PieceMove {from: Coord {x: 2, y: 0}, to: Coord {x: 1, y: 0}, piece: Piece::金将, promote: false})));
+
+    /*
+     *  ・v王 ・
+     *  ・ ・ ・
+     *  ・ 王 ・
+     */
+    let board = Board::Empty();
+    let board = board.set_grid(1, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
+
+    // 相手の王の利きには動けない
+    let actions = board.get_legal_actions();
+    assert_eq!(actions.len(), 2);
+    assert_eq!(actions[0], Action::Move(PieceMove {from: Coord {x: 1, y: 2}, to: Coord {x: 0, y: 2}, piece: Piece::王将, promote: false}));
+    assert_eq!(actions[1], Action::Move(PieceMove {from: Coord {x: 1, y: 2}, to: Coord {x: 2, y: 2}, piece: Piece::王将, promote: false}));
+}
+
+#[test]
+fn board_side_to_move_test() {
+    /*
+     *  ・v王 ・
+     *  ・ ・v金
+     *  ・ 王 ・
+     * ☗歩
+     */
+    let board = Board::Empty();
+    let board = board.set_grid(1, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.set_grid(2, 1, Grid {piece: Piece::金将, player: 1, promoted: false});
+    let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
+    let board = board.add_hand(0, Piece::歩兵, 1);
+    let gote_board = board.with_side_to_move(1);
+
+    assert_eq!(board.side_to_move(), 0);
+    assert_eq!(gote_board.side_to_move(), 1);
+    assert_eq!(board.normalize(), board);
+
+    // 後手番の盤面は反転すると先手番の盤面になり、同じ局面として扱われる
+    let normalized = gote_board.normalize();
+    assert_eq!(normalized.side_to_move(), 0);
+    assert_eq!(normalized.get_grid(0, 1), Grid {piece: Piece::金将, player: 0, promoted: false});
+    assert_eq!(normalized.get_hand_count(1, Piece::歩兵), 1);
+    assert_eq!(gote_board, normalized);
+    assert_ne!(gote_board, board);
+
+    let mut boards = HashSet::new();
+    boards.insert(gote_board);
+    assert!(boards.contains(&normalized));
+    assert!(!boards.contains(&board));
+
+    // 合法手は先手から見た座標で返す
+    let actions = gote_board.legal_moves();
+    assert_eq!(actions.len(), normalized.get_legal_actions().len());
+    assert!(actions.contains(&Action::Move(PieceMove {from: Coord {x: 2, y: 1}, to: Coord {x: 2, y: 2}, piece: Piece::金将, promote: false})));
+    assert!(!actions.iter().any(|action| matches!(action, Action::Drop(_))));
+
+    // 指した後は先手から見た向きのまま手番が入れ替わる
+    let action = Action::Move(PieceMove {from: Coord {x: 2, y: 1}, to: Coord {x: 2, y: 2}, piece: Piece::金将, promote: false});
+    let next_board = gote_board.apply(&action);
+    assert_eq!(next_board.side_to_move(), 0);
+    assert_eq!(next_board.get_grid(2, 2), Grid {piece: Piece::金将, player: 1, promoted: false});
+    assert_eq!(next_board, normalized.apply_action(&action.reverse(3, 3)));
+
+    let action = Action::Drop(PieceDrop {piece: Piece::歩兵, to: Coord {x: 0, y: 1}});
+    let next_board = board.apply(&action);
+    assert_eq!(next_board.side_to_move(), 1);
+    assert_eq!(next_board.get_grid(0, 1), Grid {piece: Piece::歩兵, player: 0, promoted: false});
+    assert_eq!(next_board, board.apply_action(&action));
+}
+
+// PieceSet#get_moves と PieceSet#get_slides から素朴に求めた移動先 (盤外・自分の駒・飛び越えを除く)
+fn get_naive_targets(board: &Board, x: u8, y: u8) -> Vec<(Coord, Coord)> {
+    let grid = board.get_grid(x, y);
+    let piece_set = board.get_rules().get_piece_set();
+    let (width, height) = board.get_size();
+    let is_inside = |target_x: i8, target_y: i8| (0..width as i8).contains(&target_x) && (0..height as i8).contains(&target_y);
+    let mut targets: Vec<(i8, i8)> = Vec::new();
+
+    for piece_move in piece_set.get_moves(&grid) {
+        targets.push((x as i8 + piece_move.x, y as i8 + piece_move.y));
+    }
+
+    for direction in piece_set.get_slides(&grid) {
+        let (mut target_x, mut target_y) = (x as i8 + direction.x, y as i8 + direction.y);
+
+        while is_inside(target_x, target_y) {
+            targets.push((target_x, target_y));
+
+            if board.get_grid(target_x as u8, target_y as u8).piece != Piece::Empty {
+                break;
+            }
+
+            target_x += direction.x;
+            target_y += direction.y;
+        }
+    }
+
+    targets.sort_by_key(|&(target_x, target_y)| (target_y, target_x));
+
+    targets.into_iter().filter_map(|(target_x, target_y)| {
+        if !is_inside(target_x, target_y) {
+            return None;
+        }
+
+        let target_grid = board.get_grid(target_x as u8, target_y as u8);
+        if target_grid.piece != Piece::Empty && target_grid.player == grid.player {
+            return None;
+        }
+
+        Some((Coord {x, y}, Coord {x: target_x as u8, y: target_y as u8}))
+    }).collect()
+}
+
+// 移動先の表から生成した手と素朴に求めた移動先が一致するかを調べる
+fn check_attack_tables(board_map: &BoardMap) {
+    for board in board_map.map.keys() {
+        let (first, second) = board.get_bitboards();
+        assert_eq!(first & second, 0);
+
+        let (width, height) = board.get_size();
+        let mut expected: Vec<(Coord, Coord)> = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let grid = board.get_grid(x, y);
+                let bit = 1 << (y * width + x);
+                assert_eq!(first & bit != 0, grid.piece != Piece::Empty && grid.player == 0);
+                assert_eq!(second & bit != 0, grid.piece != Piece::Empty && grid.player == 1);
+
+                if grid.piece != Piece::Empty && grid.player == 0 {
+                    expected.extend(get_naive_targets(board, x, y));
+                }
+            }
+        }
+
+        // 成る手と成らない手は続けて生成されるので、移動元と移動先の組にまとめる
+        let mut targets: Vec<(Coord, Coord)> = board.get_possible_moves().into_iter().map(|mov| (mov.from, mov.to)).collect();
+        targets.dedup();
+        assert_eq!(targets, expected, "{:?}", board);
+    }
+}
+
+#[test]
+fn board_get_possible_moves_attack_tables_test() {
+    check_attack_tables(&BoardMap::from_pieces(vec![Piece::飛車, Piece::角行]));
+
+    // 広い盤面では走り駒は盤の端か他の駒まで進める
+    let rules = Rules::from_name("b5x5").unwrap();
+    check_attack_tables(&BoardMap::from_pieces_with_rules(vec![Piece::飛車], &rules));
+    check_attack_tables(&BoardMap::from_pieces_with_rules(vec![Piece::角行], &rules));
+    check_attack_tables(&BoardMap::from_pieces_with_rules(vec![Piece::香車], &rules));
+
+    // 定義ファイルの駒も同じ表から生成される
+    let fairy_rules = Rules::from_name("b4x4-pfairy").unwrap();
+    check_attack_tables(&BoardMap::from_pieces_with_rules(vec![Piece::銀将], &fairy_rules));
+    check_attack_tables(&BoardMap::from_pieces_with_rules(vec![Piece::桂馬], &fairy_rules));
+
+    /*
+     *  ・ ・ ・ ・v王
+     *  ・ ・ ・ ・ ・
+     *  ・v金 ・ 飛 ・
+     *  ・ ・ ・ ・ ・
+     *  王 ・ ・ ・ ・
+     */
+    let board = Board::Empty().with_rules(&rules);
+    let board = board.set_grid(4, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.set_grid(1, 2, Grid {piece: Piece::金将, player: 1, promoted: false});
+    let board = board.set_grid(3, 2, Grid {piece: Piece::飛車, player: 0, promoted: false});
+    let board = board.set_grid(0, 4, Grid {piece: Piece::王将, player: 0, promoted: false});
+
+    // 上に2マス (敵陣の1段目には成る手もある)、下に2マス、右に1マス、左は金将を取るまでの2マス
+    let rook_moves: Vec<PieceMove> = board.get_possible_moves().into_iter().filter(|mov| mov.piece == Piece::飛車).collect();
+    assert_eq!(rook_moves.len(), 3 + 2 + 1 + 2);
+    assert!(rook_moves.contains(&PieceMove {from: Coord {x: 3, y: 2}, to: Coord {x: 3, y: 0}, piece: Piece::飛車, promote: true}));
+    assert!(rook_moves.contains(&PieceMove {from: Coord {x: 3, y: 2}, to: Coord {x: 1, y: 2}, piece: Piece::飛車, promote: false}));
+    assert!(!rook_moves.iter().any(|mov| mov.to == Coord {x: 0, y: 2}));
+}
+
+#[test]
+fn board_transitions_test() {
+    let board_map = BoardMap::from_pieces(vec![Piece::金将, Piece::歩兵]);
+
+    for board in board_map.map.keys() {
+        let mut expected: Vec<Board> = board.get_possible_moves().into_iter().filter(|mov| {
+            board.get_grid(mov.to.x, mov.to.y).piece != Piece::王将
+        }).map(|mov| board.apply_action(&Action::Move(mov))).collect();
+        expected.extend(board.get_possible_drops().into_iter().map(|drop| board.apply_action(&Action::Drop(drop))));
+
+        assert_eq!(board.transitions().collect::<Vec<_>>(), expected);
+    }
+}
+
+#[test]
+fn board_size_test() {
+    /*
+     *  ・ ・ ・v王
+     *  ・ ・ ・ ・
+     *  ・ ・ ・ ・
+     *  王 ・ ・ ・
+     */
+    let rules = Rules::from_name("b4x4").unwrap();
+    let board = Board::Empty().with_rules(&rules);
+    let board = board.set_grid(3, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.set_grid(0, 3, Grid {piece: Piece::王将, player: 0, promoted: false});
+
+    assert_eq!(board.get_size(), (4, 4));
+    assert_eq!(board.get_grid(3, 0), Grid {piece: Piece::王将, player: 1, promoted: false});
+    assert_eq!(board.get_bitboards(), (1 << 12, 1 << 3));
+    assert_eq!(board.get_possible_moves().len(), 3);
+
+    // 180度回転させると先手と後手の王が入れ替わる
+    let reversed = board.reverse();
+    assert_eq!(reversed.get_grid(0, 3), Grid {piece: Piece::王将, player: 0, promoted: false});
+    assert_eq!(reversed.get_grid(3, 0), Grid {piece: Piece::王将, player: 1, promoted: false});
+    assert_eq!(Coord {x: 0, y: 1}.reverse(4, 4), Coord {x: 3, y: 2});
+
+    // 64ビットに収まらない盤面は16バイトで書き出す
+    let blob = board.to_blob();
+    assert_eq!(blob.len(), 20);
+    assert_eq!(Board::from_blob(&blob, board.hand_layout, board.rules), board);
+    assert_eq!(Board::Empty().to_blob().len(), 12);
+
+    // 後手の手番でも手番側から見た座標で指し手を扱う
+    let gote_board = board.with_side_to_move(1);
+    let actions = gote_board.legal_moves();
+    assert_eq!(actions.len(), 3);
+    assert!(actions.contains(&Action::Move(PieceMove {from: Coord {x: 3, y: 0}, to: Coord {x: 2, y: 1}, piece: Piece::王将, promote: false})));
+
+    /*
+     *  ・v王 ・ ・ ・
+     *  ・ ・ ・ ・ ・
+     *  ・ ・ 金 ・ ・
+     *  ・ ・ ・ ・ ・
+     *  ・ ・ 王 ・ ・
+     */
+    let rules = Rules::from_name("b5x5").unwrap();
+    let board = Board::Empty().with_rules(&rules);
+    let board = board.set_grid(1, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.set_grid(2, 2, Grid {piece: Piece::金将, player: 0, promoted: false});
+    let board = board.set_grid(2, 4, Grid {piece: Piece::王将, player: 0, promoted: false});
+    assert_eq!(board.get_possible_moves().len(), 6 + 5);
+    assert!(board.is_valid());
+    assert_eq!(board.get_result(), BoardResult::Unknown);
+
+    // 王将だけの盤面はマスの数の2つの組み合わせだけある
+    let board_map = BoardMap::from_pieces_with_rules(vec![], &Rules::from_name("b3x4").unwrap());
+    assert_eq!(board_map.map.len(), 12 * 11);
+}
@@ -1,9 +1,17 @@
 extern crate nine_grids_shogi_analyzer;
+extern crate rusqlite;
 
+use std::env;
+use std::fs;
+use std::process;
 use nine_grids_shogi_analyzer::Board::{Board};
 use nine_grids_shogi_analyzer::BoardMap::{BoardMap};
 use nine_grids_shogi_analyzer::Piece::{Piece};
 use nine_grids_shogi_analyzer::Grid::{Grid};
+use nine_grids_shogi_analyzer::Material::Material;
+use nine_grids_shogi_analyzer::Rules::{Rules};
+use nine_grids_shogi_analyzer::Tablebase::{Tablebase};
+use rusqlite::Connection;
 
 #[test]
 fn board_map_from_pieces_test() {
@@ -149,3 +157,70 @@ fn board_map_from_pieces_test() {
     let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
     assert!(!board_map.map.contains_key(&board));
 }
+
+#[test]
+fn board_map_seed_test() {
+    let rules = Rules::from_name("d").unwrap();
+    let directory = env::temp_dir().join(format!("nine-grids-shogi-analyzer-seed-{}", process::id()));
+    fs::create_dir_all(&directory).unwrap();
+
+    // 金将1枚の駒構成を解いて書き出しておく
+    let mut board_map = BoardMap::from_pieces_with_rules(vec![Piece::金将], &rules);
+    board_map.solve();
+    board_map.write(directory.join("2-d.sqlite3").to_str().unwrap().to_string());
+
+    let mut tablebase = Tablebase::new(directory.to_str().unwrap().to_string());
+
+    // 解こうとしている駒構成のデータベースは読み込まない
+    let mut board_map = BoardMap::from_pieces_with_rules(vec![Piece::金将], &rules);
+    assert_eq!(board_map.seed(&mut tablebase, "2-d"), 0);
+
+    // 金将2枚の駒構成では、1枚取り除かれた後の盤面をデータベースから読み込む
+    let mut expected = BoardMap::from_pieces_with_rules(vec![Piece::金将, Piece::金将], &rules);
+    expected.solve();
+
+    let mut board_map = BoardMap::from_pieces_with_rules(vec![Piece::金将, Piece::金将], &rules);
+    assert!(board_map.seed(&mut tablebase, "22-d") > 0);
+    board_map.solve();
+
+    // 読み込んだ盤面を先に使ってしまうと最短の手数や手順の数が変わるので、すべての盤面が解き直した場合と一致することを確かめる
+    assert_eq!(board_map.map.len(), expected.map.len());
+    assert_eq!((board_map.wins, board_map.loses), (expected.wins, expected.loses));
+
+    for (board, state) in expected.map.iter() {
+        assert_eq!(board_map.map.get(board), Some(state), "{:?}", board);
+    }
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn board_map_read_test() {
+    let directory = env::temp_dir().join(format!("nine-grids-shogi-analyzer-read-{}", process::id()));
+    fs::create_dir_all(&directory).unwrap();
+
+    for (rules_name, pieces) in [("", vec![Piece::金将, Piece::歩兵]), ("z2-d", vec![Piece::銀将, Piece::銀将])].iter() {
+        let rules = Rules::from_name(rules_name).unwrap();
+        let mut board_map = BoardMap::from_pieces_with_rules(pieces.clone(), &rules);
+        board_map.solve();
+
+        let database_name = Material::from_board(board_map.map.keys().next().unwrap()).get_database_name(&rules);
+        let path = directory.join(format!("{}.sqlite3", database_name));
+        let path = path.to_str().unwrap();
+        board_map.write(path.to_string());
+
+        // 書き出されていない深さ0の盤面も含めて元に戻る
+        let read_map = BoardMap::read(path).unwrap();
+        assert!(board_map.map.values().any(|state| state.depth == Some(0)));
+        assert_eq!(read_map.map, board_map.map);
+        assert_eq!((read_map.wins, read_map.loses), (board_map.wins, board_map.loses));
+
+        // meta テーブルのない古いデータベースはファイル名から駒構成とルールを求める
+        Connection::open(path).unwrap().execute_batch("DROP TABLE meta").unwrap();
+        assert_eq!(BoardMap::read(path).unwrap().map, board_map.map);
+    }
+
+    assert!(BoardMap::read(directory.join("missing.sqlite3").to_str().unwrap()).is_err());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
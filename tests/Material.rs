@@ -0,0 +1,90 @@
+extern crate nine_grids_shogi_analyzer;
+
+use nine_grids_shogi_analyzer::BoardMap::BoardMap;
+use nine_grids_shogi_analyzer::Material::Material;
+use nine_grids_shogi_analyzer::Piece::Piece;
+use nine_grids_shogi_analyzer::PieceSet::STANDARD_PIECE_SET;
+use nine_grids_shogi_analyzer::Rules::{Rules, DEFAULT_RULES};
+use nine_grids_shogi_analyzer::Tablebase::Tablebase;
+use nine_grids_shogi_analyzer::Usi::parse_sfen;
+use nine_grids_shogi_analyzer::util::DEFAULT_HAND_CAPACITIES;
+
+#[test]
+fn material_from_board_test() {
+    // 成った駒は成る前の駒として数え、両者の持ち駒も数える
+    let material = Material::from_board(&parse_sfen("k2/1+P1/1K1 b Gs 1").unwrap());
+    assert_eq!(material.to_pieces(), vec![Piece::金将, Piece::銀将, Piece::歩兵]);
+    assert_eq!(material.get_name(), "236");
+    assert_eq!(material.len(), 3);
+    assert_eq!(material.get_count(Piece::歩兵), 1);
+    assert_eq!(material.get_count(Piece::飛車), 0);
+
+    // 手番や駒の持ち主によらず同じ駒構成になる
+    assert_eq!(Material::from_board(&parse_sfen("k2/1+p1/1K1 w gS 1").unwrap()), material);
+    assert_eq!(Material::from_board(&parse_sfen("k2/3/1K1 b GSP 1").unwrap()), material);
+
+    let kings = Material::from_board(&parse_sfen("k2/3/1K1 b - 1").unwrap());
+    assert!(kings.is_empty());
+    // 王将だけの駒構成のファイル名も隠しファイルにならない
+    assert_eq!(kings.get_name(), "K");
+    assert_eq!(kings.get_database_name(&Rules::from_name("d").unwrap()), "K-d");
+
+    // 駒構成から生成した盤面はすべて同じ駒構成になる
+    let board_map = BoardMap::from_pieces(vec![Piece::歩兵, Piece::金将]);
+    let expected = Material::from_pieces(&[Piece::金将, Piece::歩兵]);
+    assert!(board_map.map.keys().all(|board| Material::from_board(board) == expected));
+}
+
+#[test]
+fn material_name_test() {
+    // 駒の並べ方によらず、持ち駒の番号順の名前になる
+    let material = Material::from_pieces(&[Piece::歩兵, Piece::金将, Piece::王将, Piece::桂馬, Piece::銀将]);
+    assert_eq!(material.get_name(), "2346");
+    assert_eq!(material.to_string(), "2346");
+    assert_eq!(Material::from_name("6432"), Ok(material));
+    assert_eq!(Material::from_name(&material.get_name()), Ok(material));
+    assert!(Material::from_name("27").is_err());
+    assert!(Material::from_name("2a").is_err());
+    assert_eq!(Material::from_name("K"), Ok(Material::default()));
+    assert_eq!(Material::from_name(""), Ok(Material::default()));
+    assert!(Material::from_name("2K").is_err());
+
+    assert_eq!(material.get_database_name(&DEFAULT_RULES), "2346");
+    assert_eq!(material.get_database_name(&Rules::from_name("z2-d").unwrap()), "2346-z2-d");
+
+    // 駒の名前は PieceSet#get_name の表記でも USI の表記でもよい
+    let standard = &STANDARD_PIECE_SET;
+    assert_eq!(material.get_piece_names(standard), vec!["金", "銀", "桂", "歩"]);
+    assert_eq!(Material::from_piece_names(&material.get_piece_names(standard), standard), Ok(material));
+    assert_eq!(Material::from_piece_names(&["歩", "G", "n", "銀"], standard), Ok(material));
+    assert!(Material::from_piece_names(&["王"], standard).is_err());
+    assert!(Material::from_piece_names(&["金銀"], standard).is_err());
+    assert!(Material::from_piece_names(&["X"], standard).is_err());
+
+    // 駒の名前はルールの駒の定義に従う
+    let fairy = Rules::from_name("pfairy").unwrap().get_piece_set();
+    assert_eq!(material.get_piece_names(fairy), vec!["金", "象", "騎", "歩"]);
+    assert_eq!(Material::from_piece_names(&["象", "騎", "金", "歩"], fairy), Ok(material));
+    assert_eq!(Material::from_piece_names(&["E"], fairy), Ok(Material::from_pieces(&[Piece::銀将])));
+    assert!(Material::from_piece_names(&["銀"], fairy).is_err());
+    assert!(Material::from_piece_names(&["S"], fairy).is_err());
+
+    // u8 に収まらない枚数は表せない
+    assert!(Material::from_name(&"6".repeat(256)).is_err());
+    assert_eq!(Material::from_name(&"6".repeat(255)).unwrap().get_count(Piece::歩兵), 255);
+    assert_eq!(Material::from_pieces(&[Piece::歩兵; 300]).get_count(Piece::歩兵), 255);
+}
+
+#[test]
+fn material_ordering_test() {
+    // Tablebase::enumerate_configs と同じ順番に並ぶ
+    let configs = Tablebase::enumerate_configs(3, DEFAULT_HAND_CAPACITIES);
+    let materials: Vec<Material> = configs.iter().map(|pieces| Material::from_pieces(pieces)).collect();
+    let mut sorted = materials.clone();
+    sorted.reverse();
+    sorted.sort();
+    assert_eq!(sorted, materials);
+
+    assert!(Material::from_name("6").unwrap() < Material::from_name("01").unwrap());
+    assert!(Material::from_name("06").unwrap() < Material::from_name("11").unwrap());
+}
@@ -0,0 +1,41 @@
+extern crate nine_grids_shogi_analyzer;
+
+use nine_grids_shogi_analyzer::Perft::{perft, divide, PerftCount};
+use nine_grids_shogi_analyzer::Usi::{parse_sfen};
+
+#[test]
+fn perft_test() {
+    for line in include_str!("perft.txt").lines() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(';').collect();
+        let board = parse_sfen(fields[0]).unwrap();
+        let depth: u8 = fields[1].parse().unwrap();
+        let numbers: Vec<u64> = fields[2..].iter().map(|field| field.parse().unwrap()).collect();
+
+        let expected = PerftCount {
+            nodes: numbers[0],
+            moves: numbers[1],
+            captures: numbers[2],
+            promotions: numbers[3],
+            drops: numbers[4],
+        };
+
+        assert_eq!(perft(&board, depth), expected, "perft({}) of {}", depth, fields[0]);
+    }
+}
+
+#[test]
+fn divide_test() {
+    let board = parse_sfen("k2/1Pg/+PK1 b S2Pb 1").unwrap();
+
+    let mut total = PerftCount::default();
+    for (_, count) in divide(&board, 3) {
+        total += count;
+    }
+
+    assert_eq!(total, perft(&board, 3));
+    assert_eq!(divide(&board, 1).len(), 11);
+}
@@ -0,0 +1,175 @@
+extern crate nine_grids_shogi_analyzer;
+
+use nine_grids_shogi_analyzer::Board::{Action, Board, BoardResult, Coord, PieceDrop, PieceMove};
+use nine_grids_shogi_analyzer::BoardMap::BoardMap;
+use nine_grids_shogi_analyzer::Grid::{Grid, Move};
+use nine_grids_shogi_analyzer::Piece::{Piece};
+use nine_grids_shogi_analyzer::PieceSet::{PieceSet, STANDARD_PIECE_SET};
+use nine_grids_shogi_analyzer::Rules::{Rules, DEFAULT_RULES};
+use nine_grids_shogi_analyzer::Usi::{action_to_usi, parse_sfen_with_rules, to_sfen};
+use nine_grids_shogi_analyzer::util::DEFAULT_HAND_CAPACITIES;
+
+const 酔象: &str = "
+# 銀将の代わりに酔象を使う
+[銀将]
+name = 象
+usi = e
+moves = -1,-1 0,-1 1,-1 -1,0 1,0 -1,1 1,1  # 真後ろには動けない
+promoted_name = 太
+promoted_moves = -1,-1 0,-1 1,-1 -1,0 1,0 -1,1 0,1 1,1
+capacity = 3
+";
+
+#[test]
+fn piece_set_standard_test() {
+    assert_eq!(STANDARD_PIECE_SET.validate(), Ok(()));
+    assert_eq!(STANDARD_PIECE_SET.get_capacities(), DEFAULT_HAND_CAPACITIES);
+    assert_eq!(STANDARD_PIECE_SET.get_forced_promotion_rows(), DEFAULT_RULES.forced_promotion_rows);
+    assert_eq!(DEFAULT_RULES.get_piece_set(), &STANDARD_PIECE_SET);
+
+    for hand_index in 0..7 {
+        let piece = Piece::from_hand_index(hand_index);
+        assert_eq!(STANDARD_PIECE_SET.get_usi_char(piece), piece.to_usi_char());
+        assert_eq!(STANDARD_PIECE_SET.from_usi_char(piece.to_usi_char().to_ascii_lowercase()), Some(piece));
+        assert_eq!(STANDARD_PIECE_SET.get_name(piece, false).to_string(), piece.to_char());
+        assert_eq!(STANDARD_PIECE_SET.is_promotable(piece), piece.is_promotable());
+    }
+
+    // 後手の駒は向きが反対になる
+    let knight = Grid {piece: Piece::桂馬, player: 1, promoted: false};
+    assert_eq!(knight.get_moves(), vec![Move {x: 1, y: 2}, Move {x: -1, y: 2}]);
+    let lance = Grid {piece: Piece::香車, player: 1, promoted: false};
+    assert_eq!(lance.get_slides(), vec![Move {x: 0, y: 1}]);
+    assert_eq!(STANDARD_PIECE_SET.get_name(Piece::角行, true), '馬');
+}
+
+#[test]
+fn piece_set_parse_test() {
+    let piece_set = PieceSet::parse("suizo", 酔象).unwrap();
+    let elephant = piece_set.get_definition(Piece::銀将);
+
+    assert_eq!(piece_set.name, "suizo");
+    assert_eq!((elephant.name, elephant.usi, elephant.capacity), ('象', 'E', 3));
+    assert_eq!(piece_set.get_name(Piece::銀将, true), '太');
+    assert_eq!(piece_set.get_moves(&Grid {piece: Piece::銀将, player: 0, promoted: false}).len(), 7);
+    assert!(!piece_set.get_moves(&Grid {piece: Piece::銀将, player: 0, promoted: false}).contains(&Move {x: 0, y: 1}));
+    assert!(piece_set.get_moves(&Grid {piece: Piece::銀将, player: 1, promoted: false}).contains(&Move {x: 0, y: 1}));
+    assert_eq!(piece_set.get_moves(&Grid {piece: Piece::銀将, player: 0, promoted: true}).len(), 8);
+    assert_eq!(piece_set.from_usi_char('e'), Some(Piece::銀将));
+    assert_eq!(piece_set.from_usi_char('S'), None);
+    assert_eq!(piece_set.get_capacities(), [2, 2, 4, 3, 4, 4, 7]);
+
+    // 定義しなかった駒は標準のまま
+    assert_eq!(piece_set.pieces[4], STANDARD_PIECE_SET.pieces[4]);
+    assert_eq!(PieceSet::parse("knight", "[N]\nmoves = -2,-1 2,-1").unwrap().get_forced_promotion_rows(), [0, 0, 0, 0, 0, 1, 1]);
+
+    assert!(PieceSet::parse("x", "[玉将]\nname = 玉").is_err());
+    assert!(PieceSet::parse("x", "name = 象").is_err());
+    assert!(PieceSet::parse("x", "[S]\n[S]").is_err());
+    assert!(PieceSet::parse("x", "[S]\nmoves = 3,0").is_err());
+    assert!(PieceSet::parse("x", "[S]\nmoves = 0,0").is_err());
+    assert!(PieceSet::parse("x", "[S]\nslides = up").is_err());
+    assert!(PieceSet::parse("x", "[S]\nspeed = 1").is_err());
+    // 成った後の名前がない
+    assert!(PieceSet::parse("x", "[S]\npromoted_moves = 0,-1").is_err());
+    // 金将の代わりの駒は成れない
+    assert!(PieceSet::parse("x", "[G]\npromoted_name = 成").is_err());
+    // USIの表記が重複している
+    assert!(PieceSet::parse("x", "[S]\nusi = G").is_err());
+    assert!(PieceSet::parse("x", "[S]\nusi = K").is_err());
+    // 成れない駒は行き所のない駒にできない
+    assert!(PieceSet::parse("x", "[N]\nforced_promotion_rows = 2").is_err());
+    assert!(PieceSet::parse("x", "[P]\ncapacity = 16").is_err());
+    assert!(PieceSet::parse("x-y", "").is_err());
+}
+
+#[test]
+fn piece_set_rules_test() {
+    let rules = Rules::from_name("pfairy").unwrap();
+    let piece_set = rules.get_piece_set();
+
+    assert_eq!(piece_set.name, "fairy");
+    assert_eq!(rules.get_name(), "pfairy");
+    assert_eq!(Rules::from_name(&rules.get_name()), Ok(rules));
+    // ナイトは成れないので行き所のない段はない
+    assert_eq!(rules.forced_promotion_rows, [0, 0, 0, 0, 0, 1, 1]);
+    assert_eq!(Rules::from_name("f0000001-b4x4-pfairy").unwrap().get_name(), "b4x4-pfairy-f0000001");
+    assert!(Rules::from_name("pfairy-f0000211").is_err());
+    assert!(Rules::from_name("pmissing").is_err());
+
+    // pieces ディレクトリの外のファイルは読まない
+    let message = PieceSet::load("../pieces/fairy").unwrap_err();
+    assert!(message.contains("letters, digits and underscores"), "{}", message);
+    assert!(Rules::from_name("p../../etc/passwd").is_err());
+
+    // 登録済みの駒の定義を読み込み直しても名前の文字列は同じものを使う
+    assert_eq!(PieceSet::load("fairy").unwrap().name.as_ptr(), piece_set.name.as_ptr());
+
+    let suizo = PieceSet::parse("suizo", 酔象).unwrap();
+    let rules = DEFAULT_RULES.with_piece_set(&suizo);
+    assert_eq!(rules.get_name(), "psuizo");
+    assert_eq!(rules.with_piece_set(&STANDARD_PIECE_SET), DEFAULT_RULES);
+}
+
+#[test]
+fn piece_set_moves_test() {
+    /*
+     *  ・v王 ・
+     *  ・ 騎 ・
+     *  ・ ・ 王
+     */
+    let rules = Rules::from_name("pfairy").unwrap();
+    let board = Board::Empty().with_rules(&rules);
+    let board = board.set_grid(1, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.set_grid(2, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
+
+    // ナイトは後ろにも跳べ、成らない
+    let knight = board.set_grid(1, 1, Grid {piece: Piece::桂馬, player: 0, promoted: false});
+    let moves: Vec<PieceMove> = knight.get_possible_moves().into_iter().filter(|mov| mov.piece == Piece::桂馬).collect();
+    assert_eq!(moves, vec![]);
+    let knight = board.set_grid(0, 2, Grid {piece: Piece::桂馬, player: 0, promoted: false});
+    let moves: Vec<PieceMove> = knight.get_possible_moves().into_iter().filter(|mov| mov.piece == Piece::桂馬).collect();
+    assert_eq!(moves, vec![
+        PieceMove {piece: Piece::桂馬, from: Coord {x: 0, y: 2}, to: Coord {x: 1, y: 0}, promote: false},
+        PieceMove {piece: Piece::桂馬, from: Coord {x: 0, y: 2}, to: Coord {x: 2, y: 1}, promote: false},
+    ]);
+    assert!(knight.set_grid(0, 0, Grid {piece: Piece::桂馬, player: 0, promoted: false}).is_valid());
+
+    // 酔象は真後ろに動けず、敵陣に入ると太子に成れる
+    let elephant = board.set_grid(1, 1, Grid {piece: Piece::銀将, player: 0, promoted: false});
+    let moves: Vec<PieceMove> = elephant.get_possible_moves().into_iter().filter(|mov| mov.piece == Piece::銀将).collect();
+    assert_eq!(moves.len(), 9);
+    assert!(!moves.iter().any(|mov| mov.to == Coord {x: 1, y: 2}));
+    assert!(moves.contains(&PieceMove {piece: Piece::銀将, from: Coord {x: 1, y: 1}, to: Coord {x: 0, y: 0}, promote: true}));
+
+    // USIの表記も定義に従う
+    let board = parse_sfen_with_rules("1k1/1E1/2K b N 1", &rules).unwrap();
+    assert_eq!(board.get_grid(1, 1), Grid {piece: Piece::銀将, player: 0, promoted: false});
+    assert_eq!(to_sfen(&board), "1k1/1E1/2K b N 1");
+    assert_eq!(action_to_usi(&board, &Action::Drop(PieceDrop {piece: Piece::桂馬, to: Coord {x: 0, y: 2}})), "N*3c");
+    assert!(parse_sfen_with_rules("1k1/1S1/2K b - 1", &rules).is_err());
+    assert!(parse_sfen_with_rules("1k1/1+N1/2K b - 1", &rules).is_err());
+}
+
+#[test]
+fn piece_set_board_map_test() {
+    let rules = Rules::from_name("pfairy").unwrap();
+    let board_map = BoardMap::from_pieces_with_rules(vec![Piece::桂馬], &rules);
+    let default_board_map = BoardMap::from_pieces(vec![Piece::桂馬]);
+
+    // 成れない駒の成った盤面はなく、どの段にも置ける
+    assert!(board_map.map.keys().all(|board| board.get_rules() == &rules));
+    assert!(board_map.map.keys().any(|board| board.get_grid(0, 0).piece == Piece::桂馬 && board.get_grid(0, 0).player == 0));
+    assert!(!board_map.map.keys().any(|board| (0..3).any(|x| (0..3).any(|y| board.get_grid(x, y).promoted))));
+    assert!(board_map.map.len() < default_board_map.map.len());
+
+    for board in board_map.map.keys() {
+        if board.get_result() != BoardResult::Unknown {
+            continue;
+        }
+
+        for transition in board.transitions() {
+            assert!(transition.get_result() == BoardResult::Win || board_map.map.contains_key(&transition));
+        }
+    }
+}
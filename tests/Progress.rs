@@ -0,0 +1,81 @@
+extern crate nine_grids_shogi_analyzer;
+
+use std::env;
+use std::fs;
+use std::process;
+use nine_grids_shogi_analyzer::Board::BoardResult;
+use nine_grids_shogi_analyzer::BoardMap::BoardMap;
+use nine_grids_shogi_analyzer::Piece::Piece;
+use nine_grids_shogi_analyzer::Progress::{Progress, Stage, TerminalProgress};
+use nine_grids_shogi_analyzer::Rules::DEFAULT_RULES;
+
+// 報告された処理ごとに、全体の数と最後に報告された数を記録する
+#[derive(Default)]
+struct RecordingProgress {
+    stages: Vec<(Stage, Option<usize>, usize)>,
+    is_running: bool,
+}
+
+impl Progress for RecordingProgress {
+    fn start(&mut self, stage: Stage, total: Option<usize>) {
+        assert!(!self.is_running);
+        self.is_running = true;
+        self.stages.push((stage, total, 0));
+    }
+
+    fn update(&mut self, count: usize) {
+        assert!(self.is_running);
+        let last = self.stages.last_mut().unwrap();
+        assert!(count > last.2);
+        last.2 = count;
+    }
+
+    fn finish(&mut self) {
+        assert!(self.is_running);
+        self.is_running = false;
+    }
+}
+
+#[test]
+fn progress_board_map_test() {
+    let mut progress = RecordingProgress::default();
+    let mut board_map = BoardMap::from_pieces_with_progress(vec![Piece::金将], &DEFAULT_RULES, &mut progress);
+
+    assert_eq!(board_map.map, BoardMap::from_pieces(vec![Piece::金将]).map);
+    assert_eq!(progress.stages, vec![(Stage::Generation, None, board_map.map.len())]);
+
+    // 探索では深さごとに、まだ勝敗の決まっていない盤面をすべて調べる
+    let unknown_count = board_map.map.values().filter(|state| state.result == BoardResult::Unknown).count();
+    board_map.solve_with_progress(&mut progress);
+
+    let max_depth = board_map.map.values().filter_map(|state| state.depth).max().unwrap();
+    // 最後の回は新しく決まる盤面がないことを確かめる
+    assert_eq!(progress.stages.len(), 1 + max_depth as usize + 1);
+    assert_eq!(progress.stages[1], (Stage::Solving(1), Some(unknown_count), unknown_count));
+    assert_eq!(progress.stages.last().unwrap().0, Stage::Solving(max_depth + 1));
+
+    for &(_, total, count) in progress.stages.iter().skip(1) {
+        assert_eq!(total, Some(count));
+    }
+
+    let path = env::temp_dir().join(format!("nine-grids-shogi-analyzer-progress-{}.sqlite3", process::id()));
+    let path = path.to_str().unwrap();
+    let _ = fs::remove_file(path);
+
+    board_map.write_with_progress(path.to_string(), &mut progress);
+    assert_eq!(progress.stages.last(), Some(&(Stage::Writing, Some(board_map.map.len()), board_map.map.len())));
+    assert!(!progress.is_running);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn progress_terminal_test() {
+    // 全体の数が 0 の場合や分からない場合も表示できる
+    let mut progress = TerminalProgress::new();
+    progress.start(Stage::Solving(3), Some(0));
+    progress.finish();
+    progress.start(Stage::Generation, None);
+    progress.update(250000);
+    progress.finish();
+}
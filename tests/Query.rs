@@ -0,0 +1,90 @@
+extern crate nine_grids_shogi_analyzer;
+
+use std::env;
+use std::fs;
+use std::process;
+use nine_grids_shogi_analyzer::Board::BoardResult;
+use nine_grids_shogi_analyzer::BoardMap::BoardMap;
+use nine_grids_shogi_analyzer::Piece::Piece;
+use nine_grids_shogi_analyzer::Query::{get_material, get_principal_variation, query, Query, SortKey};
+use nine_grids_shogi_analyzer::Tablebase::Tablebase;
+use nine_grids_shogi_analyzer::Usi::parse_sfen;
+
+#[test]
+fn query_sort_test() {
+    let mut board_map = BoardMap::from_pieces(vec![Piece::金将]);
+    board_map.solve();
+
+    let max_depth = board_map.map.values().filter_map(|state| state.depth).max();
+    let results = query(&board_map, &Query::default());
+    assert_eq!(results.len(), 10);
+    assert_eq!(results[0].state.depth, max_depth);
+    assert!(results.windows(2).all(|pair| pair[0].state.depth >= pair[1].state.depth));
+
+    // 同じ条件では同じ順番になる
+    let other_results = query(&board_map, &Query::default());
+    assert!(results.iter().zip(other_results.iter()).all(|(a, b)| a.board == b.board));
+
+    let results = query(&board_map, &Query {sort_key: SortKey::Routes, limit: 5, ..Query::default()});
+    assert_eq!(results.len(), 5);
+    assert_eq!(results[0].state.routes, board_map.map.values().filter_map(|state| state.routes).max());
+    assert!(results.iter().all(|result| result.moves == result.board.legal_moves().len()));
+
+    let results = query(&board_map, &Query {sort_key: SortKey::Moves, limit: 3, ..Query::default()});
+    assert!(results.iter().all(|result| result.moves == result.board.legal_moves().len()));
+    assert_eq!(results[0].moves, board_map.map.keys().map(|board| board.legal_moves().len()).max().unwrap());
+
+    assert!(SortKey::from_name("length").is_err());
+}
+
+#[test]
+fn query_filter_test() {
+    let mut board_map = BoardMap::from_pieces(vec![Piece::金将]);
+    board_map.solve();
+
+    let query_all = |query_filter: Query| query(&board_map, &Query {limit: usize::MAX, ..query_filter});
+
+    assert_eq!(query_all(Query::default()).len(), board_map.map.len());
+    assert_eq!(query_all(Query {result: Some(BoardResult::Lose), ..Query::default()}).len() as u32, board_map.loses);
+
+    let results = query_all(Query {is_good: Some(true), ..Query::default()});
+    assert!(!results.is_empty());
+    assert!(results.iter().all(|result| result.state.is_good == Some(true)));
+
+    // 手番側が金将を持っている盤面と持っていない盤面に分かれる
+    let with_gold = query_all(Query {material: Some(vec![Piece::金将]), ..Query::default()});
+    let without_gold = query_all(Query {material: Some(vec![]), ..Query::default()});
+    assert_eq!(with_gold.len() + without_gold.len(), board_map.map.len());
+    assert!(with_gold.iter().all(|result| get_material(&result.board) == vec![Piece::金将]));
+
+    // 成った駒は成る前の駒として数え、相手の駒は数えない
+    assert_eq!(get_material(&parse_sfen("k2/1+P1/1K1 b GP 1").unwrap()), vec![Piece::金将, Piece::歩兵, Piece::歩兵]);
+    assert_eq!(get_material(&parse_sfen("k2/1+P1/1K1 w GP 1").unwrap()), vec![]);
+}
+
+#[test]
+fn query_principal_variation_test() {
+    let directory = env::temp_dir().join(format!("nine-grids-shogi-analyzer-query-{}", process::id()));
+    fs::create_dir_all(&directory).unwrap();
+
+    let mut board_map = BoardMap::from_pieces(vec![Piece::金将]);
+    board_map.solve();
+    board_map.write(directory.join("2.sqlite3").to_str().unwrap().to_string());
+
+    let mut tablebase = Tablebase::new(directory.to_str().unwrap().to_string());
+
+    // 読み筋は、最後に指した側が次に王将を取れる盤面で終わる
+    let result = query(&board_map, &Query::default())[0];
+    let depth = result.state.depth.unwrap();
+    let variation = get_principal_variation(&mut tablebase, &result.board, 100);
+    assert_eq!(variation.len(), depth as usize - 1);
+
+    let board = variation.iter().fold(result.board, |board, action| board.apply(action));
+    let state = tablebase.probe(&board).unwrap();
+    assert_eq!((state.result, state.depth), (BoardResult::Lose, Some(1)));
+
+    // 読み筋は max_length で打ち切る
+    assert_eq!(get_principal_variation(&mut tablebase, &result.board, 2).len(), 2);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,261 @@
+extern crate nine_grids_shogi_analyzer;
+
+use nine_grids_shogi_analyzer::Board::{Action, Board, BoardResult, Coord, PieceDrop, PieceMove};
+use nine_grids_shogi_analyzer::BoardMap::BoardMap;
+use nine_grids_shogi_analyzer::Grid::{Grid};
+use nine_grids_shogi_analyzer::Piece::{Piece};
+use nine_grids_shogi_analyzer::Rules::{Rules, Stalemate, DEFAULT_RULES};
+use nine_grids_shogi_analyzer::Search::Search;
+
+#[test]
+fn rules_name_test() {
+    assert_eq!(Rules::from_name(""), Ok(DEFAULT_RULES));
+    assert_eq!(DEFAULT_RULES.get_name(), "");
+
+    let rules = Rules::from_name("z2-f0000111-m").unwrap();
+    assert_eq!(rules.promotion_rows, 2);
+    assert_eq!(rules.forced_promotion_rows, [0, 0, 0, 0, 1, 1, 1]);
+    assert!(!rules.allow_non_promotion);
+    assert_eq!(rules.get_name(), "z2-f0000111-m");
+    assert_eq!(Rules::from_name(&rules.get_name()), Ok(rules));
+
+    assert!(Rules::from_name("z4").is_err());
+    // 金将は成れないので行き所のない段を設定できない
+    assert!(Rules::from_name("f0010000").is_err());
+    assert!(Rules::from_name("f000011").is_err());
+    assert!(Rules::from_name("x1").is_err());
+    // ASCII 以外の文字で始まる表記もエラーにする
+    assert!(Rules::from_name("é").is_err());
+    assert!(Rules::from_name("d-é1").is_err());
+    assert!(Rules::from_name("z2-歩").is_err());
+
+    let rules = Rules::from_name("d").unwrap();
+    assert!(!rules.allow_drops);
+    assert_eq!(rules.get_name(), "d");
+
+    let rules = Rules::from_name("b4x5-z2").unwrap();
+    assert_eq!((rules.width, rules.height), (4, 5));
+    assert_eq!(rules.get_squares(), 20);
+    assert_eq!(rules.get_name(), "b4x5-z2");
+    assert_eq!(Rules::from_name("b3x3"), Ok(DEFAULT_RULES));
+    assert!(Rules::from_name("b6x6").is_err());
+    assert!(Rules::from_name("b1x1").is_err());
+    assert!(Rules::from_name("b4").is_err());
+    assert!(Rules::from_name("b4x4x4").is_err());
+    // 段の数は盤の高さまで設定できる
+    assert!(Rules::from_name("b5x5-z4").is_ok());
+
+    assert_eq!(Rules::from_name("sl"), Ok(DEFAULT_RULES));
+    let rules = Rules::from_name("d-sw").unwrap();
+    assert_eq!(rules.stalemate, Stalemate::Win);
+    assert_eq!(rules.get_name(), "d-sw");
+    assert_eq!(Rules::from_name("sd").unwrap().stalemate, Stalemate::Draw);
+    assert!(Rules::from_name("sx").is_err());
+
+    assert_eq!(Rules::get(DEFAULT_RULES.register()), &DEFAULT_RULES);
+    assert_eq!(Rules::get(rules.register()), &rules);
+}
+
+#[test]
+fn rules_promotion_test() {
+    /*
+     *  ・v王 ・
+     *  ・ 銀 ・
+     *  ・ 王 ・
+     */
+    let board = Board::Empty();
+    let board = board.set_grid(1, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.set_grid(1, 1, Grid {piece: Piece::銀将, player: 0, promoted: false});
+    let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
+
+    let silver_moves = |board: &Board| -> Vec<PieceMove> {
+        board.get_possible_moves().into_iter().filter(|mov| mov.piece == Piece::銀将).collect()
+    };
+
+    // 既定のルールでは敵陣 (1段目) に入る手だけ成れる (王を取る手も含む)
+    let moves = silver_moves(&board);
+    assert_eq!(moves.len(), 8);
+    assert!(moves.contains(&PieceMove {piece: Piece::銀将, from: Coord {x: 1, y: 1}, to: Coord {x: 0, y: 0}, promote: true}));
+    assert!(!moves.contains(&PieceMove {piece: Piece::銀将, from: Coord {x: 1, y: 1}, to: Coord {x: 0, y: 2}, promote: true}));
+
+    // 敵陣が2段なら2段目にいる駒はどこへ動いても成れる
+    let board = board.with_rules(&Rules::from_name("z2").unwrap());
+    let moves = silver_moves(&board);
+    assert_eq!(moves.len(), 10);
+    assert!(moves.contains(&PieceMove {piece: Piece::銀将, from: Coord {x: 1, y: 1}, to: Coord {x: 0, y: 2}, promote: true}));
+
+    // 不成を指せない場合は成る手だけになる
+    let board = board.with_rules(&Rules::from_name("z2-m").unwrap());
+    let moves = silver_moves(&board);
+    assert_eq!(moves.len(), 5);
+    assert!(moves.iter().all(|mov| mov.promote));
+}
+
+#[test]
+fn rules_forced_promotion_test() {
+    /*
+     *  ・v王 ・
+     *  ・ ・ ・
+     *  桂 王 ・
+     * ☗歩
+     */
+    let board = Board::Empty();
+    let board = board.set_grid(1, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
+    let board = board.add_hand(0, Piece::歩兵, 1);
+
+    // 既定のルールでは桂馬は2段目にいられない
+    let knight = Grid {piece: Piece::桂馬, player: 0, promoted: false};
+    assert!(!board.set_grid(0, 1, knight).is_valid());
+    assert!(board.set_grid(0, 2, knight).is_valid());
+
+    let rules = Rules::from_name("f0000100").unwrap();
+    let board = board.with_rules(&rules);
+
+    // 桂馬は1段目だけ、歩兵はどこにでもいられる
+    assert!(board.set_grid(0, 1, knight).is_valid());
+    assert!(!board.set_grid(0, 0, knight).is_valid());
+    assert!(board.set_grid(0, 0, Grid {piece: Piece::歩兵, player: 0, promoted: false}).is_valid());
+    assert!(board.get_possible_drops().contains(&PieceDrop {piece: Piece::歩兵, to: Coord {x: 0, y: 0}}));
+
+    // 香車は1段目に成らずに進める
+    let lance_moves = |board: &Board| -> Vec<PieceMove> {
+        board.set_grid(0, 2, Grid {piece: Piece::香車, player: 0, promoted: false}).get_possible_moves().into_iter().filter(|mov| mov.piece == Piece::香車).collect()
+    };
+    assert_eq!(lance_moves(&board).len(), 3);
+    assert!(lance_moves(&board).contains(&PieceMove {piece: Piece::香車, from: Coord {x: 0, y: 2}, to: Coord {x: 0, y: 0}, promote: false}));
+    assert_eq!(lance_moves(&board.with_rules(&DEFAULT_RULES)), vec![
+        PieceMove {piece: Piece::香車, from: Coord {x: 0, y: 2}, to: Coord {x: 0, y: 0}, promote: true},
+        PieceMove {piece: Piece::香車, from: Coord {x: 0, y: 2}, to: Coord {x: 0, y: 1}, promote: false},
+    ]);
+}
+
+#[test]
+fn rules_drops_test() {
+    /*
+     *  ・v王v金
+     *  ・ ・ 飛
+     *  王 ・ ・
+     * ☗歩
+     */
+    let board = Board::Empty();
+    let board = board.set_grid(1, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.set_grid(2, 0, Grid {piece: Piece::金将, player: 1, promoted: false});
+    let board = board.set_grid(2, 1, Grid {piece: Piece::飛車, player: 0, promoted: false});
+    let board = board.set_grid(0, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
+    let board = board.add_hand(0, Piece::歩兵, 1);
+    let board = board.with_rules(&Rules::from_name("d").unwrap());
+
+    // 持ち駒があっても打てない
+    assert!(board.get_possible_drops().is_empty());
+    assert!(!board.get_legal_actions().iter().any(|action| matches!(action, Action::Drop(_))));
+
+    // 取った駒は持ち駒にならない
+    let capture = PieceMove {piece: Piece::飛車, from: Coord {x: 2, y: 1}, to: Coord {x: 2, y: 0}, promote: true};
+    let board_after = board.move_piece(&capture);
+    assert_eq!(board_after.get_hand_count(0, Piece::金将), 0);
+    assert_eq!(board_after.get_hand_count(0, Piece::歩兵), 1);
+    assert_eq!(board.with_rules(&DEFAULT_RULES).move_piece(&capture).get_hand_count(0, Piece::金将), 1);
+
+    // 打つ手しかない盤面はステルスメイトになる
+    /*
+     *  ・ ・v王
+     *  ・ ・ ・
+     *  ・ ・ ・
+     * ☗歩
+     */
+    let board = Board::Empty();
+    let board = board.set_grid(2, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.add_hand(0, Piece::歩兵, 1);
+    assert_eq!(board.get_result(), BoardResult::Unknown);
+    assert_eq!(board.with_rules(&Rules::from_name("d").unwrap()).get_result(), BoardResult::Lose);
+}
+
+#[test]
+fn rules_drops_board_map_test() {
+    let rules = Rules::from_name("d").unwrap();
+    let board_map = BoardMap::from_pieces_with_rules(vec![Piece::金将], &rules);
+    let default_board_map = BoardMap::from_pieces(vec![Piece::金将]);
+
+    // 持ち駒の盤面は生成されない
+    assert!(board_map.map.len() < default_board_map.map.len());
+    assert!(board_map.map.keys().all(|board| board.get_hand_count(0, Piece::金将) == 0 && board.get_hand_count(1, Piece::金将) == 0));
+
+    // 駒を取った後の盤面も含まれる
+    for board in board_map.map.keys() {
+        if board.get_result() != BoardResult::Unknown {
+            continue;
+        }
+
+        for transition in board.transitions() {
+            assert!(transition.get_result() == BoardResult::Win || board_map.map.contains_key(&transition));
+        }
+    }
+}
+
+#[test]
+fn rules_stalemate_test() {
+    /*
+     *  王 ・ ・
+     *  ・v金 ・
+     *  ・ ・v王
+     */
+    // 王の動ける先はすべて金将の利きなので、どう指しても王を取られる (詰み)
+    let mated = Board::Empty();
+    let mated = mated.set_grid(0, 0, Grid {piece: Piece::王将, player: 0, promoted: false});
+    let mated = mated.set_grid(1, 1, Grid {piece: Piece::金将, player: 1, promoted: false});
+    let mated = mated.set_grid(2, 2, Grid {piece: Piece::王将, player: 1, promoted: false});
+
+    /*
+     *  ・ ・v王
+     *  ・ ・ ・
+     *  ・ ・ ・
+     */
+    // 手番側には駒がないので指せる手がない (ステルスメイト)
+    let stalemate = Board::Empty().set_grid(2, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+
+    assert!(!mated.is_stalemate());
+    assert!(stalemate.is_stalemate());
+
+    for &(name, result, score) in [
+        ("", BoardResult::Lose, Some(1)),
+        ("sw", BoardResult::Win, Some(0)),
+        ("sd", BoardResult::Unknown, None),
+    ].iter() {
+        let rules = Rules::from_name(name).unwrap();
+        let mated = mated.with_rules(&rules);
+        let stalemate = stalemate.with_rules(&rules);
+
+        // 詰みはルールによらず負け
+        assert_eq!(mated.get_result(), BoardResult::Unknown);
+        assert!(mated.is_mated());
+        assert_eq!(Search::new().search(&mated, 1).0.result, BoardResult::Lose);
+
+        assert_eq!(stalemate.get_result(), result);
+        assert_eq!(stalemate.is_mated(), result == BoardResult::Lose);
+
+        let (state, action) = Search::new().search(&stalemate, 1);
+        assert_eq!(state.result, result);
+        assert_eq!(state.depth, score);
+        assert_eq!(action, None);
+    }
+
+    /*
+     *  ・ ・ ・
+     *  ・ ・ ・
+     *  王 ・ ・
+     */
+    // 相手に駒がないので、どう指しても相手はステルスメイトになる
+    // ステルスメイトを勝ちとするルールでも相手をステルスメイトにする手は (負けになるが) 合法手で、王を取られる手とは区別する
+    let board = Board::Empty().set_grid(0, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
+    let moves = board.legal_moves().len();
+    assert_eq!(moves, 3);
+
+    let board = board.with_rules(&Rules::from_name("sw").unwrap());
+    assert_eq!(board.legal_moves().len(), moves);
+    assert!(board.legal_moves().iter().all(|action| {
+        let transition = board.apply(action).normalize();
+        transition.get_result() == BoardResult::Win && !transition.can_capture_king()
+    }));
+    assert_eq!(Search::new().search(&board, 2).0.result, BoardResult::Lose);
+}
@@ -0,0 +1,53 @@
+extern crate nine_grids_shogi_analyzer;
+
+use nine_grids_shogi_analyzer::Board::{Action, BoardResult, Coord, PieceDrop};
+use nine_grids_shogi_analyzer::Piece::{Piece};
+use nine_grids_shogi_analyzer::Search::{Search};
+use nine_grids_shogi_analyzer::Usi::{parse_sfen};
+
+#[test]
+fn search_test() {
+    /*
+     *  ・v王 ・
+     *  ・ ・ ・
+     *  ・ 王 ・
+     * ☗金
+     */
+    let board = parse_sfen("1k1/3/1K1 b G 1").unwrap();
+    let mut search = Search::new();
+    let (state, action) = search.search(&board, 5);
+    assert_eq!(state.result, BoardResult::Win);
+    assert_eq!(state.depth, Some(2));
+    assert_eq!(action, Some(Action::Drop(PieceDrop {piece: Piece::金将, to: Coord {x: 1, y: 1}})));
+
+    /*
+     *  ・ ・ ・
+     * v王 ・ ・
+     *  ・ ・ 王
+     * ☗金
+     */
+    let board = parse_sfen("3/k2/2K b G 1").unwrap();
+    let mut search = Search::new();
+    let (state, _) = search.search(&board, 5);
+    assert_eq!(state.result, BoardResult::Win);
+    assert_eq!(state.depth, Some(4));
+    assert_eq!(search.get_principal_variation(&board, 10).len(), 3);
+
+    // 金を打たれた後の盤面は負け
+    let board = board.apply_action(&Action::Drop(PieceDrop {piece: Piece::金将, to: Coord {x: 1, y: 1}}));
+    let mut search = Search::new();
+    let (state, _) = search.search(&board, 5);
+    assert_eq!(state.result, BoardResult::Lose);
+
+    /*
+     *  ・v王 ・
+     *  ・ ・ ・
+     *  ・ 王 ・
+     */
+    // 王将だけでは勝敗がつかない
+    let board = parse_sfen("1k1/3/1K1 b - 1").unwrap();
+    let mut search = Search::new();
+    let (state, action) = search.search(&board, 4);
+    assert_eq!(state.result, BoardResult::Unknown);
+    assert!(action.is_some());
+}
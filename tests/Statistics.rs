@@ -0,0 +1,84 @@
+extern crate nine_grids_shogi_analyzer;
+
+use std::time::Duration;
+use nine_grids_shogi_analyzer::Board::BoardResult;
+use nine_grids_shogi_analyzer::BoardMap::BoardMap;
+use nine_grids_shogi_analyzer::Piece::Piece;
+use nine_grids_shogi_analyzer::Statistics::Statistics;
+
+#[test]
+fn statistics_from_board_map_test() {
+    let mut board_map = BoardMap::from_pieces(vec![Piece::金将]);
+    let 打ち歩詰め_count = board_map.solve();
+    let statistics = Statistics::from_board_map("2", "", &board_map, 打ち歩詰め_count);
+
+    assert_eq!(statistics.boards as usize, board_map.map.len());
+    assert_eq!(statistics.get_win_count(), board_map.wins);
+    assert_eq!(statistics.get_lose_count(), board_map.loses);
+    assert_eq!(statistics.get_win_count() + statistics.get_lose_count() + statistics.unknowns, statistics.boards);
+    assert_eq!(statistics.is_good_count as usize, board_map.map.values().filter(|state| state.is_good == Some(true)).count());
+
+    // 深さごとの数は最大の深さまでの同じ長さの一覧になる
+    let max_depth = board_map.map.values().filter_map(|state| state.depth).max();
+    assert_eq!(statistics.max_depth, max_depth);
+    assert_eq!(statistics.wins.len(), max_depth.unwrap() as usize + 1);
+    assert_eq!(statistics.loses.len(), statistics.wins.len());
+    assert_eq!(statistics.wins[0] as usize, board_map.map.values().filter(|state| state.result == BoardResult::Win && state.depth == Some(0)).count());
+    assert_eq!(statistics.loses[1] as usize, board_map.map.values().filter(|state| state.result == BoardResult::Lose && state.depth == Some(1)).count());
+}
+
+#[test]
+fn statistics_output_test() {
+    let statistics = Statistics {
+        config: "2".to_string(),
+        rules: "d".to_string(),
+        boards: 10,
+        wins: vec![4, 0, 2],
+        loses: vec![0, 3, 0],
+        unknowns: 1,
+        打ち歩詰め_count: 5,
+        is_good_count: 2,
+        max_depth: Some(2),
+        generation_time: Duration::from_millis(1500),
+        seed_time: Duration::default(),
+        solve_time: Duration::from_millis(250),
+        write_time: Duration::from_millis(5),
+    };
+
+    assert_eq!(statistics.to_json(), "\
+{
+  \"config\": \"2\",
+  \"rules\": \"d\",
+  \"boards\": 10,
+  \"wins\": 6,
+  \"loses\": 3,
+  \"unknowns\": 1,
+  \"uchifuzume\": 5,
+  \"is_good\": 2,
+  \"max_depth\": 2,
+  \"wins_by_depth\": [4, 0, 2],
+  \"loses_by_depth\": [0, 3, 0],
+  \"seconds\": {
+    \"generation\": 1.500,
+    \"seed\": 0.000,
+    \"solve\": 0.250,
+    \"write\": 0.005
+  }
+}
+");
+
+    let csv = statistics.to_csv();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "metric,depth,value");
+    assert!(lines.contains(&"unknowns,,1"));
+    assert!(lines.contains(&"uchifuzume,,5"));
+    assert!(lines.contains(&"generation_seconds,,1.500"));
+    assert!(lines.contains(&"wins,2,2"));
+    assert!(lines.contains(&"loses,1,3"));
+    assert_eq!(lines.len(), 1 + 11 + 2 * 3);
+
+    // 解けた盤面がない場合
+    let statistics = Statistics {max_depth: None, wins: vec![], loses: vec![], ..statistics};
+    assert!(statistics.to_json().contains("\"max_depth\": null,"));
+    assert!(statistics.to_csv().contains("max_depth,,\n"));
+}
@@ -0,0 +1,230 @@
+extern crate nine_grids_shogi_analyzer;
+extern crate rusqlite;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use nine_grids_shogi_analyzer::Board::{Board, BoardResult};
+use nine_grids_shogi_analyzer::BoardMap::{BoardMap, BoardState};
+use nine_grids_shogi_analyzer::Material::Material;
+use nine_grids_shogi_analyzer::Piece::{Piece};
+use nine_grids_shogi_analyzer::Rules::Rules;
+use nine_grids_shogi_analyzer::Tablebase::{DatabaseSummary, Tablebase};
+use nine_grids_shogi_analyzer::Usi::{parse_sfen, parse_sfen_with_rules, to_sfen};
+use nine_grids_shogi_analyzer::util::DEFAULT_HAND_CAPACITIES;
+use rusqlite::Connection;
+
+#[test]
+fn tablebase_enumerate_configs_test() {
+    assert_eq!(Tablebase::enumerate_configs(0, DEFAULT_HAND_CAPACITIES), vec![vec![]]);
+
+    // 駒の数が 0, 1, 2 枚の順に、同じ枚数では持ち駒の番号順に並ぶ
+    let configs = Tablebase::enumerate_configs(2, DEFAULT_HAND_CAPACITIES);
+    assert_eq!(configs.len(), 1 + 7 + 28);
+    assert_eq!(configs[1], vec![Piece::飛車]);
+    assert_eq!(configs[8], vec![Piece::飛車, Piece::飛車]);
+    assert_eq!(configs[35], vec![Piece::歩兵, Piece::歩兵]);
+
+    // 最大枚数を超える駒構成は含まない
+    let configs = Tablebase::enumerate_configs(3, DEFAULT_HAND_CAPACITIES);
+    assert!(configs.contains(&vec![Piece::金将, Piece::金将, Piece::金将]));
+    assert!(!configs.contains(&vec![Piece::飛車, Piece::飛車, Piece::飛車]));
+    assert_eq!(Tablebase::enumerate_configs(3, [0, 0, 1, 0, 0, 0, 2]), vec![
+        vec![],
+        vec![Piece::金将],
+        vec![Piece::歩兵],
+        vec![Piece::金将, Piece::歩兵],
+        vec![Piece::歩兵, Piece::歩兵],
+        vec![Piece::金将, Piece::歩兵, Piece::歩兵],
+    ]);
+}
+
+#[test]
+fn tablebase_parse_database_name_test() {
+
+    // ルールの表記にも "-" が含まれる
+    let (material, rules) = Tablebase::parse_database_name("26-z2-d").unwrap();
+    assert_eq!(material.to_pieces(), vec![Piece::金将, Piece::歩兵]);
+    assert_eq!(rules, Rules::from_name("z2-d").unwrap());
+    assert_eq!(Tablebase::get_database_name("26", &rules), "26-z2-d");
+    assert_eq!(Tablebase::parse_database_name(""), Ok((Material::default(), Rules::default())));
+    assert_eq!(Tablebase::parse_database_name("K-d"), Ok((Material::default(), Rules::from_name("d").unwrap())));
+    assert!(Tablebase::parse_database_name("2-x").is_err());
+}
+
+#[test]
+fn tablebase_summarize_test() {
+    let path = env::temp_dir().join(format!("nine-grids-shogi-analyzer-summarize-{}.sqlite3", std::process::id()));
+    let path = path.to_str().unwrap();
+    let _ = fs::remove_file(path);
+
+    // 金将1枚の盤面に勝ち負けの状態を書き込む (深さ0の盤面は書き出されない)
+    let mut board_map = BoardMap::from_pieces(vec![Piece::金将]);
+    let boards: Vec<Board> = board_map.map.iter().filter(|&(_, state)| state.result == BoardResult::Unknown).map(|(&board, _)| board).take(3).collect();
+    let states = [(BoardResult::Win, 5), (BoardResult::Win, 3), (BoardResult::Lose, 4)];
+
+    for (board, &(result, depth)) in boards.iter().zip(states.iter()) {
+        board_map.map.insert(*board, BoardState {result, depth: Some(depth), routes: Some(1), is_good: Some(false)});
+    }
+
+    let unknowns = board_map.map.values().filter(|state| state.result == BoardResult::Unknown).count() as u32;
+    let loses = board_map.map.values().filter(|state| state.result == BoardResult::Lose).count() as u32;
+    board_map.write(path.to_string());
+
+    assert_eq!(Tablebase::summarize(path), Ok(DatabaseSummary {
+        wins: 2,
+        loses,
+        unknowns,
+        max_win_depth: Some(5),
+        max_lose_depth: Some(4),
+    }));
+
+    fs::remove_file(path).unwrap();
+    assert!(Tablebase::summarize(path).is_err());
+}
+
+#[test]
+fn tablebase_write_schema_test() {
+    let path = env::temp_dir().join(format!("nine-grids-shogi-analyzer-schema-{}.sqlite3", std::process::id()));
+    let path = path.to_str().unwrap();
+    let _ = fs::remove_file(path);
+
+    let rules = Rules::from_name("z2-d").unwrap();
+    let mut board_map = BoardMap::from_pieces_with_rules(vec![Piece::金将, Piece::歩兵], &rules);
+    board_map.solve();
+    board_map.write(path.to_string());
+
+    // 駒が取り除かれた盤面も含まれるが、駒構成は最も駒の多いもの
+    let meta = Tablebase::read_meta(path).unwrap();
+    assert_eq!(meta["config"], "26");
+    assert_eq!(meta["rules"], "z2-d");
+    // 標準の駒の定義の名前は空
+    assert_eq!((meta["width"].as_str(), meta["height"].as_str(), meta["pieces"].as_str()), ("3", "3", ""));
+    assert_eq!((meta["promotion_rows"].as_str(), meta["forced_promotion_rows"].as_str()), ("2", "0000211"));
+    assert_eq!((meta["allow_non_promotion"].as_str(), meta["allow_drops"].as_str(), meta["stalemate"].as_str()), ("true", "false", "lose"));
+    assert_eq!(meta["version"], env!("CARGO_PKG_VERSION"));
+    assert!(meta["created_at"].parse::<u64>().unwrap() > 0);
+
+    // SFEN の列と索引
+    let conn = Connection::open(path).unwrap();
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM boards", [], |row| row.get(0)).unwrap();
+    assert_eq!(count as usize, board_map.map.values().filter(|state| state.depth != Some(0)).count());
+
+    let (blob, sfen): (Vec<u8>, String) = conn.query_row("SELECT board, sfen FROM boards WHERE depth = 3 LIMIT 1", [], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+    let board = *board_map.map.keys().find(|board| board.to_blob() == blob).unwrap();
+    assert_eq!(sfen, to_sfen(&board));
+
+    let indexes: Vec<String> = conn.prepare("SELECT name FROM sqlite_master WHERE type = 'index' AND name LIKE 'boards_%' ORDER BY name").unwrap()
+        .query_map([], |row| row.get(0)).unwrap().map(|name| name.unwrap()).collect();
+    assert_eq!(indexes, vec!["boards_result_depth", "boards_routes"]);
+
+    // 書き出し直すと置き換わる
+    BoardMap::from_pieces_with_rules(vec![Piece::金将], &rules).write(path.to_string());
+    assert_eq!(Tablebase::read_meta(path).unwrap()["config"], "2");
+    assert_eq!(Tablebase::summarize(path).unwrap().wins, 0);
+
+    drop(conn);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn tablebase_merge_test() {
+    let directory = env::temp_dir().join(format!("nine-grids-shogi-analyzer-merge-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+
+    let drop_rules = Rules::from_name("d").unwrap();
+    let mut board_maps = [
+        BoardMap::from_pieces(vec![Piece::金将]),
+        BoardMap::from_pieces_with_rules(vec![Piece::金将], &drop_rules),
+        BoardMap::from_pieces(vec![Piece::銀将]),
+    ];
+
+    for board_map in board_maps.iter_mut() {
+        board_map.solve();
+    }
+
+    board_maps[0].write(directory.join("2.sqlite3").to_str().unwrap().to_string());
+    board_maps[1].write(directory.join("2-d.sqlite3").to_str().unwrap().to_string());
+    // meta テーブルのないデータベースはファイル名から駒構成を求める
+    board_maps[2].write(directory.join("3.sqlite3").to_str().unwrap().to_string());
+    Connection::open(directory.join("3.sqlite3")).unwrap().execute_batch("DROP TABLE meta").unwrap();
+    // 駒構成の分からないファイルはまとめない
+    fs::write(directory.join("notes.sqlite3"), "").unwrap();
+
+    let output = directory.join("combined.sqlite3");
+    let output = output.to_str().unwrap();
+    let summary = Tablebase::merge(directory.to_str().unwrap(), output).unwrap();
+    let materials: Vec<&str> = summary.materials.iter().map(|(material, _)| material.as_str()).collect();
+    assert_eq!(materials, vec!["2-d", "2", "3"]);
+    assert_eq!(summary.materials[1].1, Tablebase::summarize(directory.join("2.sqlite3").to_str().unwrap()).map(|summary| summary.wins + summary.loses + summary.unknowns).unwrap());
+    assert_eq!(summary.skipped.len(), 1);
+    assert!(summary.skipped[0].0.ends_with("notes.sqlite3"));
+
+    // まとめたデータベースでも駒構成ごとのデータベースと同じ状態が引ける
+    let mut tablebase = Tablebase::new(directory.to_str().unwrap().to_string());
+    let mut combined = Tablebase::new(output.to_string());
+
+    for board_map in board_maps.iter() {
+        for (board, &state) in board_map.map.iter() {
+            assert_eq!(combined.probe(board), Some(state));
+
+            if let Some(directory_state) = tablebase.probe(board) {
+                assert_eq!(directory_state, state);
+            }
+        }
+    }
+
+    // 持ち駒を打てないルールでは、駒を取って小さくなった駒構成の盤面も盤面ごとの駒構成で引ける
+    let kings = parse_sfen_with_rules("k2/3/2K b - 1", &drop_rules).unwrap();
+    assert_eq!(Material::from_board(&kings).get_database_name(&drop_rules), "K-d");
+    assert_eq!(combined.probe(&kings), Some(board_maps[1].map[&kings.normalize()]));
+    assert_eq!(Connection::open(output).unwrap().query_row("SELECT boards FROM tables WHERE material = 'K-d'", [], |row| row.get::<_, u32>(0)).unwrap(),
+        board_maps[1].map.iter().filter(|&(board, state)| state.depth != Some(0) && Material::from_board(board).is_empty()).count() as u32);
+
+    assert_eq!(combined.probe(&parse_sfen("k2/3/1K1 b N 1").unwrap()), None);
+
+    // まとめ直すと置き換わり、まとめたデータベース自身は含まない
+    fs::remove_file(directory.join("3.sqlite3")).unwrap();
+    let summary = Tablebase::merge(directory.to_str().unwrap(), output).unwrap();
+    assert_eq!(summary.materials.len(), 2);
+    assert_eq!(summary.skipped.len(), 1);
+    assert!(!Path::new(&format!("{}.tmp", output)).exists());
+
+    // まとめたデータベース以外のファイルは出力先に指定しても上書きしない
+    let database = directory.join("2.sqlite3");
+    let database = database.to_str().unwrap();
+    let contents = fs::read(database).unwrap();
+    assert!(Tablebase::merge(directory.to_str().unwrap(), database).is_err());
+    assert_eq!(fs::read(database).unwrap(), contents);
+    assert!(!Path::new(&format!("{}.tmp", database)).exists());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn tablebase_probe_after_capture_test() {
+    let directory = env::temp_dir().join(format!("nine-grids-shogi-analyzer-capture-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+
+    // 持ち駒を打てないルールでは、駒を取った後の王将だけの盤面は K-d のデータベースではなく 2-d のデータベースにある
+    let rules = Rules::from_name("d").unwrap();
+    let mut board_map = BoardMap::from_pieces_with_rules(vec![Piece::金将], &rules);
+    board_map.solve();
+    board_map.write(directory.join("2-d.sqlite3").to_str().unwrap().to_string());
+
+    let mut tablebase = Tablebase::new(directory.to_str().unwrap().to_string());
+    let board = parse_sfen_with_rules("k2/3/1Kg b - 1", &rules).unwrap();
+    let captured = board.legal_moves().iter().map(|action| board.apply(action)).find(|transition| Material::from_board(transition).is_empty()).unwrap();
+
+    assert!(!directory.join("K-d.sqlite3").exists());
+    assert_eq!(tablebase.probe(&captured), Some(board_map.map[&captured.normalize()]));
+
+    // 駒を取る手も含めて、すべての手の遷移先を引ける
+    let (state, action) = tablebase.get_best_action(&board).unwrap();
+    assert_eq!(state, board_map.map[&board.normalize()]);
+    assert!(action.is_some());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,149 @@
+extern crate nine_grids_shogi_analyzer;
+
+use nine_grids_shogi_analyzer::Board::{Action, Board, Coord, PieceDrop, PieceMove};
+use nine_grids_shogi_analyzer::Piece::{Piece};
+use nine_grids_shogi_analyzer::Grid::{Grid};
+use nine_grids_shogi_analyzer::Rules::{Rules};
+use nine_grids_shogi_analyzer::Usi::{parse_sfen, parse_sfen_with_rules, to_sfen, action_to_usi, parse_action};
+
+#[test]
+fn parse_sfen_test() {
+    /*
+     * v王 ・ ・
+     *  ・ 歩v金
+     *  と 王 ・
+     * ☗銀歩歩
+     * ☖角
+     */
+    let board = Board::Empty();
+    let board = board.set_grid(0, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.set_grid(1, 1, Grid {piece: Piece::歩兵, player: 0, promoted: false});
+    let board = board.set_grid(2, 1, Grid {piece: Piece::金将, player: 1, promoted: false});
+    let board = board.set_grid(0, 2, Grid {piece: Piece::歩兵, player: 0, promoted: true});
+    let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
+    let board = board.add_hand(0, Piece::銀将, 1);
+    let board = board.add_hand(0, Piece::歩兵, 2);
+    let board = board.add_hand(1, Piece::角行, 1);
+
+    assert_eq!(parse_sfen("k2/1Pg/+PK1 w S2Pb 1"), Ok(board.with_side_to_move(1)));
+    assert_eq!(parse_sfen("k2/1Pg/+PK1 b S2Pb"), Ok(board));
+    assert_eq!(parse_sfen("k2/1Pg/+PK1 w S2Pb 1").unwrap().side_to_move(), 1);
+    assert_eq!(to_sfen(&board.with_side_to_move(1)), "k2/1Pg/+PK1 w S2Pb 1");
+
+    let board = Board::Empty();
+    let board = board.set_grid(1, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
+    assert_eq!(parse_sfen("1k1/3/1K1 b - 1"), Ok(board));
+    assert_eq!(to_sfen(&board), "1k1/3/1K1 b - 1");
+
+    // 既定の最大枚数を超える駒構成では持ち駒の表現を広げる
+    let board = parse_sfen("1k1/3/1K1 b 2Rr 1").unwrap();
+    assert_eq!(board.get_hand_layout().capacities[0], 3);
+    assert_eq!(board.get_hand_count(0, Piece::飛車), 2);
+    assert_eq!(board.get_hand_count(1, Piece::飛車), 1);
+    assert_eq!(to_sfen(&board), "1k1/3/1K1 b 2Rr 1");
+
+    // 段の数が足りない
+    assert!(parse_sfen("1k1/1K1 b - 1").is_err());
+    // 王が足りない
+    assert!(parse_sfen("1k1/3/3 b - 1").is_err());
+    // 行き所のない駒
+    assert!(parse_sfen("Pk1/3/1K1 b - 1").is_err());
+    // 金は成れない
+    assert!(parse_sfen("1k1/+G2/1K1 b - 1").is_err());
+    // 持ち駒の表現で扱えない枚数
+    assert!(parse_sfen("1k1/3/1K1 b 16P 1").is_err());
+    assert!(parse_sfen("1k1/3/1K1 b 2 1").is_err());
+    // 盤の幅を超える空きマス (数字を足すと u8 に収まらない場合も)
+    assert!(parse_sfen("1k1/4/1K1 b - 1").is_err());
+    assert!(parse_sfen(&format!("1k1/{}/1K1 b - 1", "9".repeat(29))).is_err());
+}
+
+#[test]
+fn action_to_usi_test() {
+    /*
+     *  ・v王 ・
+     *  ・ ・ ・
+     *  ・ 王 銀
+     */
+    let board = Board::Empty();
+    let board = board.set_grid(1, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
+    let board = board.set_grid(2, 1, Grid {piece: Piece::銀将, player: 0, promoted: false});
+
+    let mov = Action::Move(PieceMove {piece: Piece::銀将, from: Coord {x: 2, y: 1}, to: Coord {x: 2, y: 0}, promote: false});
+    assert_eq!(action_to_usi(&board, &mov), "1b1a");
+
+    let mov = Action::Move(PieceMove {piece: Piece::銀将, from: Coord {x: 2, y: 1}, to: Coord {x: 2, y: 0}, promote: true});
+    assert_eq!(action_to_usi(&board, &mov), "1b1a+");
+
+    let drop = Action::Drop(PieceDrop {piece: Piece::歩兵, to: Coord {x: 0, y: 1}});
+    assert_eq!(action_to_usi(&board, &drop), "P*3b");
+
+    /*
+     *  ・v王 ・
+     * v銀 ・ ・
+     *  ・ 王 ・
+     */
+    // 後手番でも座標は先手から見た向きで表す
+    let board = Board::Empty();
+    let board = board.set_grid(1, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
+    let board = board.set_grid(0, 1, Grid {piece: Piece::銀将, player: 1, promoted: false});
+    let board = board.with_side_to_move(1);
+
+    let mov = Action::Move(PieceMove {piece: Piece::銀将, from: Coord {x: 0, y: 1}, to: Coord {x: 0, y: 2}, promote: false});
+    assert_eq!(action_to_usi(&board, &mov), "3b3c");
+}
+
+#[test]
+fn parse_action_test() {
+    /*
+     *  ・v王 ・
+     *  ・ ・ ・
+     *  ・ 王 ・
+     * ☗金
+     */
+    let board = Board::Empty();
+    let board = board.set_grid(1, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
+    let board = board.add_hand(0, Piece::金将, 1);
+
+    assert_eq!(parse_action(&board, "G*2b"), Ok(Action::Drop(PieceDrop {piece: Piece::金将, to: Coord {x: 1, y: 1}})));
+    assert_eq!(parse_action(&board, "2c3c"), Ok(Action::Move(PieceMove {piece: Piece::王将, from: Coord {x: 1, y: 2}, to: Coord {x: 0, y: 2}, promote: false})));
+
+    // 王を相手の王の利きに動かす手は指せない
+    assert!(parse_action(&board, "2c2b").is_err());
+    // 持っていない駒は打てない
+    assert!(parse_action(&board, "S*2b").is_err());
+
+    // 後手番では後手の駒を動かす
+    let board = board.add_hand(0, Piece::金将, -1).add_hand(1, Piece::金将, 1).with_side_to_move(1);
+    assert_eq!(parse_action(&board, "G*2b"), Ok(Action::Drop(PieceDrop {piece: Piece::金将, to: Coord {x: 1, y: 1}})));
+    assert_eq!(parse_action(&board, "2a3a"), Ok(Action::Move(PieceMove {piece: Piece::王将, from: Coord {x: 1, y: 0}, to: Coord {x: 0, y: 0}, promote: false})));
+    assert!(parse_action(&board, "2c3c").is_err());
+}
+
+#[test]
+fn sfen_board_size_test() {
+    /*
+     *  ・ ・ ・v王
+     *  ・ ・ ・ ・
+     *  ・ ・ ・ ・
+     *  王 ・ ・ ・
+     */
+    let rules = Rules::from_name("b4x4").unwrap();
+    let board = parse_sfen_with_rules("3k/4/4/K3 b G 1", &rules).unwrap();
+    assert_eq!(board.get_size(), (4, 4));
+    assert_eq!(board.get_grid(3, 0).piece, Piece::王将);
+    assert_eq!(to_sfen(&board), "3k/4/4/K3 b G 1");
+
+    // 筋は盤の右端から数える
+    let action = Action::Move(PieceMove {from: Coord {x: 0, y: 3}, to: Coord {x: 1, y: 2}, piece: Piece::王将, promote: false});
+    assert_eq!(action_to_usi(&board, &action), "4d3c");
+    assert_eq!(parse_action(&board, "G*1b"), Ok(Action::Drop(PieceDrop {piece: Piece::金将, to: Coord {x: 3, y: 1}})));
+
+    // 盤の大きさがルールと合わない
+    assert!(parse_sfen("3k/4/4/K3 b G 1").is_err());
+    assert!(parse_sfen_with_rules("1k1/3/1K1 b - 1", &rules).is_err());
+}
@@ -0,0 +1,50 @@
+extern crate nine_grids_shogi_analyzer;
+
+use nine_grids_shogi_analyzer::Board::{Board, BoardResult};
+use nine_grids_shogi_analyzer::BoardMap::{BoardMap, BoardState};
+use nine_grids_shogi_analyzer::Piece::{Piece};
+use nine_grids_shogi_analyzer::Grid::{Grid};
+use nine_grids_shogi_analyzer::Verify::{verify_map};
+
+#[test]
+fn verify_map_test() {
+    // 王将だけの盤面はすべて初期盤面か勝敗のつかない盤面なので矛盾はない
+    let mut board_map = BoardMap::from_pieces(vec![]);
+    assert_eq!(verify_map(&board_map).len(), 0);
+
+    /* ・v王 ・
+       ・ ・ ・
+       ・ 王 ・ */
+    let board = Board::Empty();
+    let board = board.set_grid(1, 0, Grid {piece: Piece::王将, player: 1, promoted: false});
+    let board = board.set_grid(1, 2, Grid {piece: Piece::王将, player: 0, promoted: false});
+    board_map.map.insert(board, BoardState {
+        result: BoardResult::Win,
+        depth: Some(3),
+        routes: Some(1),
+        is_good: Some(false),
+    });
+
+    // 書き換えた盤面と、その盤面に遷移する盤面の一部が矛盾する
+    let violations = verify_map(&board_map);
+    let violation = violations.iter().find(|violation| violation.board == board).unwrap();
+    assert_eq!(violation.expected.unwrap().result, BoardResult::Unknown);
+
+    // 解く前の盤面には1手で勝てるのに Unknown となっている盤面がある
+    let board_map = BoardMap::from_pieces(vec![Piece::金将]);
+    assert!(!verify_map(&board_map).is_empty());
+
+    // 遷移先が存在しない
+    let mut board_map = BoardMap::Empty();
+    let board = board.add_hand(0, Piece::金将, 1);
+    board_map.map.insert(board, BoardState {
+        result: BoardResult::Unknown,
+        depth: None,
+        routes: None,
+        is_good: None,
+    });
+
+    let violations = verify_map(&board_map);
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].expected, None);
+}
@@ -1,6 +1,8 @@
 extern crate nine_grids_shogi_analyzer;
 
 use nine_grids_shogi_analyzer::util::{sqrt_ceil, hand_data_to_hand_info, hand_info_to_hand_data, HandInfo};
+use nine_grids_shogi_analyzer::util::{HandLayout, DEFAULT_HAND_CAPACITIES, DEFAULT_HAND_LAYOUT};
+use nine_grids_shogi_analyzer::Piece::Piece;
 
 #[test]
 fn sqrt_ceil_test() {
@@ -88,3 +90,126 @@ fn hand_info_to_hand_data_test() {
     assert_eq!(hand_info_to_hand_data(HandInfo {first: 6, second: 1}, 7), 34);
     assert_eq!(hand_info_to_hand_data(HandInfo {first: 7, second: 0}, 7), 35);
 }
+
+// 持ち駒の種類ごとに (hand_data, 先手, 後手, 入れ替えた hand_data) を列挙する
+fn hand_fields(layout: &HandLayout) -> Vec<Vec<(u32, u8, u8, u32)>> {
+    (0..7).map(|hand_type| {
+        let max_pieces = layout.capacities[hand_type];
+        let count = (max_pieces as u32 + 1) * (max_pieces as u32 + 2) / 2;
+        (0..count).map(|hand_data| {
+            let info = hand_data_to_hand_info(hand_data as u8, max_pieces);
+            let reversed = hand_info_to_hand_data(HandInfo {first: info.second, second: info.first}, max_pieces);
+            (hand_data << layout.offsets[hand_type], info.first, info.second, (reversed as u32) << layout.offsets[hand_type])
+        }).collect()
+    }).collect()
+}
+
+#[test]
+fn hand_layout_test() {
+    assert_eq!(HandLayout::new(DEFAULT_HAND_CAPACITIES), Ok(DEFAULT_HAND_LAYOUT));
+    assert_eq!(HandLayout::default(), DEFAULT_HAND_LAYOUT);
+
+    // 既定の最大枚数以下の駒構成では既定の HandLayout になる
+    assert_eq!(HandLayout::from_pieces(&[]), Ok(DEFAULT_HAND_LAYOUT));
+    assert_eq!(HandLayout::from_pieces(&[Piece::金将, Piece::金将, Piece::金将, Piece::歩兵]), Ok(DEFAULT_HAND_LAYOUT));
+
+    // 歩兵8枚: 45通り = 6ビットなので既定の HandLayout と同じビット数に収まる
+    let layout = HandLayout::from_pieces(&[Piece::歩兵; 8]).unwrap();
+    assert_eq!(layout.capacities, [2, 2, 4, 4, 4, 4, 8]);
+    assert_eq!(layout.sizes, DEFAULT_HAND_LAYOUT.sizes);
+
+    // 飛車3枚: 10通り = 4ビット
+    let layout = HandLayout::from_pieces(&[Piece::飛車, Piece::飛車, Piece::飛車]).unwrap();
+    assert_eq!(layout.capacities, [3, 2, 4, 4, 4, 4, 7]);
+    assert_eq!(layout.sizes, [4, 3, 4, 4, 4, 4, 6]);
+    assert_eq!(layout.offsets, [0, 4, 7, 11, 15, 19, 23]);
+
+    // 32ビットに収まらない駒構成や最大枚数を超える駒構成は表現できない
+    assert!(HandLayout::new([15, 15, 15, 15, 1, 0, 0]).is_err());
+    assert!(HandLayout::from_pieces(&[Piece::歩兵; 16]).is_err());
+
+    assert_eq!(DEFAULT_HAND_LAYOUT.register(), 0);
+    assert_eq!(HandLayout::get(0), &DEFAULT_HAND_LAYOUT);
+
+    let index = layout.register();
+    assert_ne!(index, 0);
+    assert_eq!(layout.register(), index);
+    assert_eq!(HandLayout::get(index), &layout);
+}
+
+// reverse_hands が hand_data_to_hand_info による計算と一致することを調べる
+// すべての hands を調べると時間がかかるので、持ち駒の種類ごとにすべての値を、他の種類の最小・最大の値や決まった組み合わせと合わせて調べる
+#[test]
+fn reverse_hands_test() {
+    let layout = DEFAULT_HAND_LAYOUT;
+    let fields = hand_fields(&layout);
+    // 他の種類の値の選び方 (線形合同法による決まった並び)
+    let mut seed: u32 = 12345;
+    let mut last = [0; 7];
+
+    for (hand_type, field) in fields.iter().enumerate() {
+        last[hand_type] = field.len() - 1;
+    }
+
+    let mut samples: Vec<[usize; 7]> = vec![[0; 7], last];
+
+    for _ in 0..64 {
+        let mut sample = [0; 7];
+
+        for (hand_type, field) in fields.iter().enumerate() {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            sample[hand_type] = (seed >> 16) as usize % field.len();
+        }
+
+        samples.push(sample);
+    }
+
+    for (hand_type, field) in fields.iter().enumerate() {
+        for &(hand_data, _, _, reversed) in field {
+            for sample in &samples {
+                let (hands, reversed_hands) = (0..7).filter(|&other| other != hand_type).fold((hand_data, reversed), |(hands, reversed_hands), other| {
+                    let (other_data, _, _, other_reversed) = fields[other][sample[other]];
+                    (hands | other_data, reversed_hands | other_reversed)
+                });
+                assert_eq!(layout.reverse_hands(hands), reversed_hands);
+            }
+        }
+    }
+}
+
+// すべての持ち駒の種類と数について、get_hand_counts と add_hand が他の種類の持ち駒を壊さずに動くことを調べる
+#[test]
+fn hand_counts_test() {
+    let layouts = [
+        DEFAULT_HAND_LAYOUT,
+        HandLayout::new([3, 0, 5, 1, 4, 2, 8]).unwrap(),
+        HandLayout::new([15, 0, 0, 0, 0, 0, 15]).unwrap(),
+    ];
+
+    for layout in &layouts {
+        let fields = hand_fields(layout);
+        let full_hands = fields.iter().fold(0, |hands, field| hands | field[field.len() - 1].0);
+
+        for (hand_type, field) in fields.iter().enumerate() {
+            let mask = ((1 << layout.sizes[hand_type]) - 1) << layout.offsets[hand_type];
+
+            for &background in &[0, full_hands & !mask] {
+                for &(hand_data, first, second, reversed) in field {
+                    let hands = background | hand_data;
+                    assert_eq!(layout.get_hand_counts(hands, hand_type), (first, second));
+                    assert_eq!(layout.reverse_hands(hands) & mask, reversed);
+
+                    // 持ち駒を増やしてから減らすと元に戻る
+                    if first + second < layout.capacities[hand_type] {
+                        for player in 0..2 {
+                            let added = layout.add_hand(hands, hand_type, player, 1);
+                            assert_eq!(layout.get_hand_counts(added, hand_type), if player == 0 {(first + 1, second)} else {(first, second + 1)});
+                            assert_eq!(added & !mask, background);
+                            assert_eq!(layout.add_hand(added, hand_type, player, -1), hands);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}